@@ -0,0 +1,69 @@
+use std::io;
+
+use chrono::{DateTime, Datelike, Days, Local, NaiveTime, TimeDelta, TimeZone, Utc};
+
+use crate::due::Due;
+
+pub fn format_age(since: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let age = now.signed_duration_since(since);
+    if age.num_minutes() < 1 {
+        "now".to_string()
+    } else if age.num_hours() < 1 {
+        format!("{}m", age.num_minutes())
+    } else if age.num_days() < 1 {
+        format!("{}h", age.num_hours())
+    } else if age.num_days() < 14 {
+        format!("{}d", age.num_days())
+    } else if age.num_days() < 60 {
+        format!("{}w", age.num_days() / 7)
+    } else if age.num_days() < 365 {
+        format!("{}mo", age.num_days() / 30)
+    } else {
+        format!("{}y", age.num_days() / 365)
+    }
+}
+
+pub fn format_ago(since: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    match format_age(since, now).as_str() {
+        "now" => "just now".to_string(),
+        age => format!("{} ago", age),
+    }
+}
+
+// A cutoff is either an age counted back from now ("30m", "12h", "7d", "2w"),
+// `week` for the start of this week (Monday), or anything `Due::parse`
+// accepts, where plain dates mean the start of that day.
+pub fn parse_cutoff(input: &str, now: DateTime<Local>) -> Result<DateTime<Utc>, io::Error> {
+    if let Some(age) = parse_age(input.trim()) {
+        return Ok((now - age).with_timezone(&Utc));
+    }
+    let due = if input.trim().eq_ignore_ascii_case("week") {
+        let today = now.date_naive();
+        Due::Date(today - Days::new(today.weekday().num_days_from_monday().into()))
+    } else {
+        Due::parse(input, now)?
+    };
+    let cutoff = match due {
+        Due::Date(date) => {
+            let start_of_day = date.and_time(NaiveTime::MIN);
+            Local
+                .from_local_datetime(&start_of_day)
+                .earliest()
+                .map_or_else(|| start_of_day.and_utc(), |local| local.with_timezone(&Utc))
+        }
+        Due::DateTime(datetime) => datetime.with_timezone(&Utc),
+    };
+    Ok(cutoff)
+}
+
+fn parse_age(input: &str) -> Option<TimeDelta> {
+    let unit_start = input.find(|c: char| !c.is_ascii_digit())?;
+    let count: i64 = input[..unit_start].parse().ok()?;
+    match &input[unit_start..] {
+        "m" | "min" => TimeDelta::try_minutes(count),
+        "h" => TimeDelta::try_hours(count),
+        "d" => TimeDelta::try_days(count),
+        "w" => TimeDelta::try_weeks(count),
+        _ => None,
+    }
+}
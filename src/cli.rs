@@ -0,0 +1,797 @@
+use std::io;
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use chrono::{DateTime, Local, Utc};
+
+use crate::age;
+use crate::color::ColorChoice;
+use crate::config::{Backend, Config};
+use crate::deps;
+use crate::due::{Due, DueView};
+use crate::filter::{self, Filter};
+use crate::format::{self, Format};
+use crate::priority::Priority;
+use crate::recur::Recurrence;
+use crate::report;
+use crate::search::{self, Search};
+use crate::status::Status;
+use crate::table::{Column, Overflow};
+use crate::tags;
+use crate::tree::{self, ChildPolicy};
+use crate::{
+    add_task_with, print_next_occurrence, print_search_hit, print_tag_counts, print_task, print_tasks, print_trashed_task, sort_tasks, task_contains, tasks_due,
+    update_from_editor, SortKey, Task, TaskManager, TaskUpdate, TodoList,
+};
+
+#[derive(Debug, Parser)]
+#[command(name = "cli-todo", version, about = "Manage a todo list from the command line")]
+#[command(after_help = "Run without a command to start the interactive menu.")]
+pub struct Cli {
+    /// Todo file to use instead of the configured or default location
+    #[arg(long, short = 'f', global = true, env = "TODO_FILE", value_name = "PATH")]
+    pub file: Option<PathBuf>,
+
+    /// Storage backend [default: from the config, else sqlite for .db/.sqlite files, else json]
+    #[arg(long, global = true, env = "TODO_BACKEND", value_enum)]
+    pub backend: Option<Backend>,
+
+    /// When to color listings [default: from the config, else auto]
+    #[arg(long, global = true, value_enum, value_name = "WHEN")]
+    pub color: Option<ColorChoice>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Add a new task
+    Add {
+        /// Task description; multiple words are joined with spaces
+        #[arg(required = true, num_args = 1..)]
+        description: Vec<String>,
+        /// Due date, e.g. 2024-05-01, "2024-05-01 14:00", tomorrow, friday or +3d
+        #[arg(long, short, value_parser = parse_due)]
+        due: Option<Due>,
+        /// Priority: none, low, medium, high or critical
+        #[arg(long, short, default_value = "none")]
+        priority: Priority,
+        /// Tag to attach, in addition to any +tag words in the description
+        #[arg(long = "tag", short, value_name = "TAG")]
+        tags: Vec<String>,
+        /// Make the new task a subtask of this one
+        #[arg(long, short = 'P', value_name = "ID")]
+        parent: Option<usize>,
+        /// Repeat the task: daily, weekly, weekly:mon,thu, monthly, monthly:15, every:3d or after:2w
+        #[arg(long, short, value_name = "RULE")]
+        recur: Option<Recurrence>,
+    },
+    /// List tasks
+    #[command(alias = "ls")]
+    List {
+        /// Only show tasks matching this filter, e.g.
+        /// 'status:open and (tag:work or priority>=high) and due<friday'
+        #[arg(value_name = "FILTER")]
+        filter: Vec<String>,
+        /// Only show open tasks (todo, in progress or blocked)
+        #[arg(long, conflicts_with_all = ["completed", "status"])]
+        pending: bool,
+        /// Only show done tasks
+        #[arg(long, conflicts_with = "status")]
+        completed: bool,
+        /// Only show tasks with this status; repeat to allow several
+        #[arg(long, short, value_name = "STATUS")]
+        status: Vec<Status>,
+        /// Only show open tasks past their due date, soonest deadline first
+        #[arg(long, group = "due_view")]
+        overdue: bool,
+        /// Only show open tasks due today
+        #[arg(long, group = "due_view")]
+        today: bool,
+        /// Only show open tasks due between now and the end of the week
+        #[arg(long, group = "due_view")]
+        week: bool,
+        /// Only show tasks with this tag; repeat to require several
+        #[arg(long = "tag", short, value_name = "TAG", value_parser = tags::parse_tag)]
+        tags: Vec<String>,
+        /// Hide tasks with this tag
+        #[arg(long = "exclude-tag", short = 'x', value_name = "TAG", value_parser = tags::parse_tag)]
+        exclude_tags: Vec<String>,
+        /// Only show tasks created before this cutoff (e.g. 30d, 2w, 2024-05-01)
+        #[arg(long, value_name = "CUTOFF", value_parser = parse_cutoff)]
+        older_than: Option<DateTime<Utc>>,
+        /// Only show tasks created since this cutoff
+        #[arg(long, value_name = "CUTOFF", value_parser = parse_cutoff)]
+        created_within: Option<DateTime<Utc>>,
+        /// Only show tasks modified since this cutoff
+        #[arg(long, value_name = "CUTOFF", value_parser = parse_cutoff)]
+        modified_within: Option<DateTime<Utc>>,
+        /// Only show tasks completed since this cutoff
+        #[arg(long, value_name = "CUTOFF", value_parser = parse_cutoff)]
+        completed_within: Option<DateTime<Utc>>,
+        /// Sort order [default: due with a due-date view, otherwise priority]
+        #[arg(long, value_enum)]
+        sort: Option<SortKey>,
+        /// Only show occurrences of this recurring series
+        #[arg(long, value_name = "ID")]
+        series: Option<usize>,
+        /// List subtasks on their own instead of under their parents
+        #[arg(long)]
+        flat: bool,
+        /// Table columns, separated by commas [default: from the config, else all]
+        #[arg(long, value_enum, value_delimiter = ',', value_name = "COLUMNS")]
+        columns: Vec<Column>,
+        /// Wrap or truncate descriptions too long for the terminal [default: from the config, else wrap]
+        #[arg(long, value_enum)]
+        overflow: Option<Overflow>,
+        /// Output format; json, jsonl, csv and tsv give every field of each task
+        #[arg(long, value_enum, default_value_t = Format::Text, long_help = format::LONG_HELP)]
+        format: Format,
+    },
+    /// Show every detail of a task
+    Show {
+        id: usize,
+        /// Output format; json, jsonl, csv and tsv give every field of each task
+        #[arg(long, value_enum, default_value_t = Format::Text, long_help = format::LONG_HELP)]
+        format: Format,
+    },
+    /// Mark one or more tasks as complete
+    Done {
+        #[arg(required = true)]
+        ids: Vec<usize>,
+        /// What to do with open subtasks [default: from the config, else block]
+        #[arg(long, value_enum, value_name = "POLICY")]
+        children: Option<ChildPolicy>,
+    },
+    /// Change the description or other fields of a task
+    Edit {
+        id: usize,
+        /// New description
+        description: Vec<String>,
+        /// New due date
+        #[arg(long, short, value_parser = parse_due, conflicts_with = "no_due")]
+        due: Option<Due>,
+        /// Remove the due date
+        #[arg(long)]
+        no_due: bool,
+        /// New priority
+        #[arg(long, short)]
+        priority: Option<Priority>,
+        /// Replace the notes
+        #[arg(long, short, conflicts_with = "no_notes")]
+        notes: Option<String>,
+        /// Remove the notes
+        #[arg(long)]
+        no_notes: bool,
+        /// Make the task a subtask of this one
+        #[arg(long, short = 'P', value_name = "ID", conflicts_with = "no_parent")]
+        parent: Option<usize>,
+        /// Move the task to the top level
+        #[arg(long)]
+        no_parent: bool,
+        /// New recurrence rule
+        #[arg(long, short, value_name = "RULE", conflicts_with = "no_recur")]
+        recur: Option<Recurrence>,
+        /// Stop the task from repeating
+        #[arg(long)]
+        no_recur: bool,
+        /// Edit the description and notes in $EDITOR
+        #[arg(long, short, conflicts_with_all = ["description", "notes", "no_notes"])]
+        editor: bool,
+    },
+    /// Set or clear the due date of a task
+    Due {
+        id: usize,
+        /// New due date
+        #[arg(value_parser = parse_due, required_unless_present = "clear")]
+        due: Option<Due>,
+        /// Remove the due date
+        #[arg(long, conflicts_with = "due")]
+        clear: bool,
+    },
+    /// Set the priority of a task
+    Priority {
+        id: usize,
+        /// none, low, medium, high or critical
+        priority: Priority,
+    },
+    /// Add (+tag) or remove (-tag) tags on a task
+    Tag {
+        id: usize,
+        #[arg(required = true, allow_hyphen_values = true, value_name = "+TAG|-TAG")]
+        changes: Vec<String>,
+    },
+    /// List all tags with open and completed task counts
+    Tags,
+    /// Make a task wait until other tasks are closed
+    Depend {
+        id: usize,
+        /// Tasks it waits on
+        #[arg(required = true, value_name = "ON")]
+        prerequisites: Vec<usize>,
+        /// Stop waiting on these tasks instead
+        #[arg(long, short)]
+        remove: bool,
+    },
+    /// List open tasks that can be worked on now
+    Next,
+    /// Show a named report from the config or a built-in one (next, overdue,
+    /// done-this-week); lists the available reports without a name
+    Report {
+        name: Option<String>,
+        /// Output format; json, jsonl, csv and tsv give every field of each task
+        #[arg(long, value_enum, default_value_t = Format::Text, long_help = format::LONG_HELP)]
+        format: Format,
+    },
+    /// Find tasks whose description or notes match the text
+    #[command(alias = "find")]
+    Search {
+        #[arg(required = true, num_args = 1..)]
+        query: Vec<String>,
+        /// How to match; fuzzy results are ranked best first
+        #[arg(long, short, value_enum, default_value_t)]
+        mode: search::Mode,
+        /// Search archived tasks too
+        #[arg(long, short)]
+        archived: bool,
+    },
+    /// Mark one or more tasks as in progress
+    Start {
+        #[arg(required = true)]
+        ids: Vec<usize>,
+    },
+    /// Mark one or more tasks as blocked
+    Block {
+        #[arg(required = true)]
+        ids: Vec<usize>,
+    },
+    /// Move one or more tasks back to todo
+    Reopen {
+        #[arg(required = true)]
+        ids: Vec<usize>,
+    },
+    /// Cancel one or more tasks
+    Cancel {
+        #[arg(required = true)]
+        ids: Vec<usize>,
+        /// What to do with open subtasks [default: from the config, else block]
+        #[arg(long, value_enum, value_name = "POLICY")]
+        children: Option<ChildPolicy>,
+    },
+    /// List, restore or purge deleted tasks
+    Trash {
+        #[command(subcommand)]
+        action: Option<TrashCommand>,
+    },
+    /// Archive completed tasks, or list, search and restore archived ones
+    Archive {
+        #[command(subcommand)]
+        action: Option<ArchiveCommand>,
+    },
+    /// Revert the most recent change
+    Undo,
+    /// Reapply the most recently undone change
+    Redo,
+    /// Upgrade the todo file to the current format
+    Migrate {
+        /// Only report what would change
+        #[arg(long, short = 'n')]
+        dry_run: bool,
+    },
+    /// Move one or more tasks to the trash
+    #[command(alias = "delete")]
+    Rm {
+        #[arg(required = true)]
+        ids: Vec<usize>,
+        /// What to do with subtasks [default: from the config, else block]
+        #[arg(long, value_enum, value_name = "POLICY")]
+        children: Option<ChildPolicy>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TrashCommand {
+    /// List tasks in the trash (the default)
+    List,
+    /// Move tasks from the trash back to the list
+    Restore {
+        #[arg(required = true)]
+        ids: Vec<usize>,
+    },
+    /// Permanently delete tasks from the trash
+    Purge {
+        /// Tasks to purge
+        #[arg(required_unless_present_any = ["all", "older_than"], conflicts_with_all = ["all", "older_than"])]
+        ids: Vec<usize>,
+        /// Purge everything in the trash
+        #[arg(long, conflicts_with = "older_than")]
+        all: bool,
+        /// Purge tasks deleted before this cutoff (e.g. 30d, 2024-05-01)
+        #[arg(long, value_name = "CUTOFF", value_parser = parse_cutoff)]
+        older_than: Option<DateTime<Utc>>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ArchiveCommand {
+    /// List archived tasks (the default)
+    List,
+    /// Find archived tasks whose description, notes or tags contain the text
+    Search {
+        #[arg(required = true, num_args = 1..)]
+        query: Vec<String>,
+    },
+    /// Move done or cancelled tasks to the archive
+    Add {
+        /// Tasks to archive
+        #[arg(required_unless_present_any = ["all", "older_than"], conflicts_with_all = ["all", "older_than"])]
+        ids: Vec<usize>,
+        /// Archive every done or cancelled task
+        #[arg(long, conflicts_with = "older_than")]
+        all: bool,
+        /// Archive tasks closed before this cutoff (e.g. 30d, 2024-05-01)
+        #[arg(long, value_name = "CUTOFF", value_parser = parse_cutoff)]
+        older_than: Option<DateTime<Utc>>,
+    },
+    /// Move archived tasks back to the list
+    Restore {
+        #[arg(required = true)]
+        ids: Vec<usize>,
+    },
+}
+
+pub fn run(command: Command, todo_list: &mut TodoList, config: &Config) -> Result<(), io::Error> {
+    match command {
+        Command::Add {
+            description,
+            due,
+            priority,
+            tags,
+            parent,
+            recur,
+        } => {
+            let update = TaskUpdate {
+                due: due.map(Some),
+                priority: Some(priority),
+                parent: parent.map(Some),
+                recurrence: recur.map(Some),
+                ..TaskUpdate::default()
+            };
+            let id = add_task_with(todo_list, description.join(" "), &tags, update)?;
+            println!("Added task with ID: {}", id);
+        }
+        Command::List {
+            filter,
+            pending,
+            completed,
+            status,
+            overdue,
+            today,
+            week,
+            tags: include,
+            exclude_tags,
+            older_than,
+            created_within,
+            modified_within,
+            completed_within,
+            sort,
+            series,
+            flat,
+            columns,
+            overflow,
+            format,
+        } => {
+            let filter = if filter.is_empty() {
+                None
+            } else {
+                Some(Filter::parse(&filter.join(" "))?)
+            };
+            let now = Local::now();
+            let context = filter::Context::new(now, todo_list.list_actionable());
+            let view = [
+                (overdue, DueView::Overdue),
+                (today, DueView::Today),
+                (week, DueView::Week),
+            ]
+            .into_iter()
+            .find_map(|(selected, view)| selected.then_some(view));
+            let tasks = match view {
+                Some(view) => tasks_due(todo_list.list_tasks(), view, now),
+                None => todo_list.list_tasks(),
+            };
+            let mut tasks: Vec<_> = tasks
+                .into_iter()
+                .filter(|task| !pending || task.status.is_open())
+                .filter(|task| !completed || task.status == Status::Done)
+                .filter(|task| status.is_empty() || status.contains(&task.status))
+                .filter(|task| tags::matches(task, &include, &exclude_tags))
+                .filter(|task| before(task.created_at, older_than))
+                .filter(|task| since(task.created_at, created_within))
+                .filter(|task| since(task.modified_at, modified_within))
+                .filter(|task| since(task.completed_at, completed_within))
+                .filter(|task| series.is_none() || task.series == series)
+                .filter(|task| filter.as_ref().is_none_or(|filter| filter.matches(task, &context)))
+                .collect();
+            let sort = sort.unwrap_or(if view.is_some() {
+                SortKey::Due
+            } else {
+                SortKey::default()
+            });
+            sort_tasks(&mut tasks, &[sort]);
+            if format != Format::Text {
+                format::print_tasks(&tasks, format)?;
+            } else if tasks.is_empty() {
+                println!("No tasks found.");
+            } else {
+                let mut layout = config.layout();
+                if !columns.is_empty() {
+                    layout.columns = columns;
+                }
+                layout.overflow = overflow.unwrap_or(layout.overflow);
+                print_tasks(&tasks, todo_list, !flat, &layout);
+            }
+        }
+        Command::Show { id, format } => {
+            let task = todo_list
+                .get_task(id)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Task not found"))?;
+            match format {
+                Format::Text => print_details(task, todo_list),
+                format => format::print_task(task, format)?,
+            }
+        }
+        Command::Done { ids, children } => {
+            set_child_policy(todo_list, children);
+            let next = todo_list.batch(|list| {
+                ids.iter().map(|&id| list.complete_task(id)).collect::<Result<Vec<_>, _>>()
+            })?;
+            for (id, next) in ids.into_iter().zip(next) {
+                println!("Marked task {} as complete", id);
+                print_next_occurrence(todo_list, next);
+            }
+        }
+        Command::Start { ids } => {
+            todo_list.batch(|list| ids.iter().try_for_each(|&id| list.start_task(id)))?;
+            for id in ids {
+                println!("Started task {}", id);
+            }
+        }
+        Command::Block { ids } => {
+            todo_list.batch(|list| ids.iter().try_for_each(|&id| list.block_task(id)))?;
+            for id in ids {
+                println!("Marked task {} as blocked", id);
+            }
+        }
+        Command::Reopen { ids } => {
+            todo_list.batch(|list| ids.iter().try_for_each(|&id| list.reopen_task(id)))?;
+            for id in ids {
+                println!("Reopened task {}", id);
+            }
+        }
+        Command::Cancel { ids, children } => {
+            set_child_policy(todo_list, children);
+            todo_list.batch(|list| ids.iter().try_for_each(|&id| list.cancel_task(id)))?;
+            for id in ids {
+                println!("Cancelled task {}", id);
+            }
+        }
+        Command::Edit {
+            id,
+            description,
+            due,
+            no_due,
+            priority,
+            notes,
+            no_notes,
+            parent,
+            no_parent,
+            recur,
+            no_recur,
+            editor,
+        } => {
+            let mut update = TaskUpdate {
+                description: (!description.is_empty()).then(|| description.join(" ")),
+                notes: if no_notes { Some(None) } else { notes.map(Some) },
+                due: if no_due { Some(None) } else { due.map(Some) },
+                priority,
+                parent: if no_parent { Some(None) } else { parent.map(Some) },
+                recurrence: if no_recur { Some(None) } else { recur.map(Some) },
+                ..TaskUpdate::default()
+            };
+            if editor {
+                let task = todo_list
+                    .get_task(id)
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Task not found"))?;
+                match update_from_editor(task)? {
+                    Some(edited) => {
+                        update.description = edited.description;
+                        update.notes = edited.notes;
+                    }
+                    None => {
+                        println!("Empty description, task {} left unchanged", id);
+                        return Ok(());
+                    }
+                }
+            }
+            if update.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Nothing to change; pass a new description, a field flag or --editor",
+                ));
+            }
+            todo_list.update_task(id, update)?;
+            println!("Updated task {}", id);
+        }
+        Command::Due { id, due, clear: _ } => {
+            todo_list.update_task(id, TaskUpdate { due: Some(due), ..TaskUpdate::default() })?;
+            match due {
+                Some(due) => println!("Task {} is due {}", id, due),
+                None => println!("Cleared due date of task {}", id),
+            }
+        }
+        Command::Priority { id, priority } => {
+            let update = TaskUpdate {
+                priority: Some(priority),
+                ..TaskUpdate::default()
+            };
+            todo_list.update_task(id, update)?;
+            println!("Set priority of task {} to {}", id, priority);
+        }
+        Command::Tag { id, changes } => {
+            let current = todo_list
+                .get_task(id)
+                .map(|task| task.tags.clone())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Task not found"))?;
+            let update = TaskUpdate {
+                tags: Some(tags::apply_changes(&current, &changes)?),
+                ..TaskUpdate::default()
+            };
+            todo_list.update_task(id, update)?;
+            println!("Updated tags of task {}", id);
+        }
+        Command::Tags => print_tag_counts(todo_list.list_tasks()),
+        Command::Depend {
+            id,
+            prerequisites,
+            remove,
+        } => {
+            let mut depends_on = todo_list
+                .get_task(id)
+                .map(|task| task.depends_on.clone())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Task not found"))?;
+            if remove {
+                depends_on.retain(|id| !prerequisites.contains(id));
+            } else {
+                depends_on.extend(&prerequisites);
+            }
+            let update = TaskUpdate {
+                depends_on: Some(depends_on),
+                ..TaskUpdate::default()
+            };
+            todo_list.update_task(id, update)?;
+            println!("Updated dependencies of task {}", id);
+        }
+        Command::Search {
+            query,
+            mode,
+            archived,
+        } => {
+            let search = Search::new(&query.join(" "), mode)?;
+            let archive = if archived { todo_list.list_archive()? } else { Vec::new() };
+            let hits = search.run(todo_list.list_tasks().into_iter().chain(&archive));
+            if hits.is_empty() {
+                println!("No matching tasks.");
+            }
+            let layout = config.layout();
+            hits.iter().for_each(|hit| print_search_hit(hit, layout.theme.as_ref()));
+        }
+        Command::Next => {
+            let mut tasks = todo_list.list_actionable();
+            sort_tasks(&mut tasks, &[SortKey::Priority]);
+            if tasks.is_empty() {
+                println!("Nothing to do right now.");
+            } else {
+                print_tasks(&tasks, todo_list, false, &config.layout());
+            }
+        }
+        Command::Report { name, format } => {
+            let reports = report::all(&config.reports);
+            let Some(name) = name else {
+                let width = reports.keys().map(|name| name.chars().count()).max().unwrap_or(0);
+                for (name, report) in &reports {
+                    let description = report.description.as_deref().unwrap_or_default();
+                    let line = format!("{:width$}  {}", name, description, width = width);
+                    println!("{}", line.trim_end());
+                }
+                return Ok(());
+            };
+            let report = reports.get(&name).ok_or_else(|| {
+                let names: Vec<&str> = reports.keys().map(String::as_str).collect();
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Unknown report '{}' (available: {})", name, names.join(", ")),
+                )
+            })?;
+            let filter = report.filter(&name)?;
+            let context = filter::Context::new(Local::now(), todo_list.list_actionable());
+            let mut tasks: Vec<&Task> = todo_list
+                .list_tasks()
+                .into_iter()
+                .filter(|task| filter.as_ref().is_none_or(|filter| filter.matches(task, &context)))
+                .collect();
+            sort_tasks(&mut tasks, report.sort());
+            if let Some(limit) = report.limit {
+                tasks.truncate(limit);
+            }
+            if format != Format::Text {
+                format::print_tasks(&tasks, format)?;
+            } else if tasks.is_empty() {
+                println!("No tasks found.");
+            } else if report.columns.is_empty() {
+                print_tasks(&tasks, todo_list, false, &config.layout());
+            } else {
+                let layout = config.layout().with_columns(&report.columns);
+                print_tasks(&tasks, todo_list, false, &layout);
+            }
+        }
+        Command::Trash { action } => match action.unwrap_or(TrashCommand::List) {
+            TrashCommand::List => {
+                let tasks = todo_list.list_trash();
+                if tasks.is_empty() {
+                    println!("Trash is empty.");
+                }
+                for task in tasks {
+                    print_trashed_task(task);
+                }
+            }
+            TrashCommand::Restore { ids } => {
+                todo_list.batch(|list| ids.iter().try_for_each(|&id| list.restore_task(id)))?;
+                for id in ids {
+                    println!("Restored task {}", id);
+                }
+            }
+            TrashCommand::Purge {
+                ids,
+                all: _,
+                older_than,
+            } => {
+                if ids.is_empty() {
+                    let count = todo_list.purge_trash(older_than)?;
+                    println!("Purged {} task(s)", count);
+                } else {
+                    todo_list.batch(|list| ids.iter().try_for_each(|&id| list.purge_task(id)))?;
+                    for id in ids {
+                        println!("Purged task {}", id);
+                    }
+                }
+            }
+        },
+        Command::Archive { action } => match action.unwrap_or(ArchiveCommand::List) {
+            ArchiveCommand::List => print_archived(&todo_list.list_archive()?, ""),
+            ArchiveCommand::Search { query } => {
+                print_archived(&todo_list.list_archive()?, &query.join(" "))
+            }
+            ArchiveCommand::Add {
+                ids,
+                all: _,
+                older_than,
+            } => {
+                if ids.is_empty() {
+                    let archived = todo_list.archive_completed(older_than)?;
+                    println!("Archived {} task(s)", archived.len());
+                } else {
+                    todo_list.archive_tasks(&ids)?;
+                    for id in ids {
+                        println!("Archived task {}", id);
+                    }
+                }
+            }
+            ArchiveCommand::Restore { ids } => {
+                for id in ids {
+                    todo_list.unarchive_task(id)?;
+                    println!("Restored task {} from the archive", id);
+                }
+            }
+        },
+        Command::Undo => match todo_list.undo()? {
+            Some(entry) => println!("Undid: {}", entry.summary()),
+            None => println!("Nothing to undo."),
+        },
+        Command::Redo => match todo_list.redo()? {
+            Some(entry) => println!("Redid: {}", entry.summary()),
+            None => println!("Nothing to redo."),
+        },
+        Command::Migrate { dry_run } => {
+            let steps = todo_list.migrate_storage(dry_run)?;
+            if steps.is_empty() {
+                println!("Already up to date; nothing to migrate.");
+            } else {
+                println!("{}", if dry_run { "Would apply:" } else { "Applied:" });
+                for step in steps {
+                    println!("  {}", step);
+                }
+            }
+        }
+        Command::Rm { ids, children } => {
+            set_child_policy(todo_list, children);
+            todo_list.batch(|list| ids.iter().try_for_each(|&id| list.delete_task(id)))?;
+            for id in ids {
+                println!("Moved task {} to the trash", id);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn set_child_policy(todo_list: &mut TodoList, policy: Option<ChildPolicy>) {
+    if let Some(policy) = policy {
+        todo_list.child_policy = policy;
+    }
+}
+
+fn print_details(task: &Task, todo_list: &TodoList) {
+    print_task(task);
+    if let Some(notes) = &task.notes {
+        println!("  Notes:");
+        for line in notes.lines() {
+            println!("    {}", line);
+        }
+    }
+    if let Some(parent) = task.parent {
+        println!("  Subtask of: {}", parent);
+    }
+    if let Some((done, total)) = tree::progress(todo_list.list_tasks(), task.id.0) {
+        println!("  Subtasks: {}/{} done", done, total);
+    }
+    if !task.depends_on.is_empty() {
+        let ids: Vec<usize> = task.depends_on.iter().copied().collect();
+        println!("  Depends on: {}", deps::format_ids(&ids));
+    }
+    if let Some(series) = task.series {
+        println!("  Series: {}", series);
+    }
+    let now = Utc::now();
+    for (label, at) in [
+        ("Created", task.created_at),
+        ("Modified", task.modified_at),
+        ("Completed", task.completed_at),
+    ] {
+        if let Some(at) = at {
+            println!(
+                "  {}: {} ({})",
+                label,
+                at.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                age::format_ago(at, now)
+            );
+        }
+    }
+}
+
+fn print_archived(tasks: &[Task], query: &str) {
+    let tasks: Vec<&Task> = tasks.iter().filter(|task| task_contains(task, query)).collect();
+    if tasks.is_empty() {
+        println!("No archived tasks found.");
+    }
+    for task in tasks {
+        print_task(task);
+    }
+}
+
+fn parse_due(input: &str) -> Result<Due, io::Error> {
+    Due::parse(input, Local::now())
+}
+
+fn parse_cutoff(input: &str) -> Result<DateTime<Utc>, io::Error> {
+    age::parse_cutoff(input, Local::now())
+}
+
+// With a cutoff set, tasks without the timestamp never match.
+fn since(timestamp: Option<DateTime<Utc>>, cutoff: Option<DateTime<Utc>>) -> bool {
+    cutoff.is_none_or(|cutoff| timestamp.is_some_and(|timestamp| timestamp >= cutoff))
+}
+
+fn before(timestamp: Option<DateTime<Utc>>, cutoff: Option<DateTime<Utc>>) -> bool {
+    cutoff.is_none_or(|cutoff| timestamp.is_some_and(|timestamp| timestamp < cutoff))
+}
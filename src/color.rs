@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+use std::io::{self, Error, ErrorKind, IsTerminal};
+use std::str::FromStr;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Deserializer};
+
+use crate::priority::Priority;
+use crate::status::Status;
+use crate::Task;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    // Color a terminal unless NO_COLOR is set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(&self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            // An empty NO_COLOR does not count as set (see no-color.org).
+            ColorChoice::Auto => {
+                io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            }
+        }
+    }
+}
+
+// An ANSI text style written as words such as "bold red" or "dim": any of
+// bold, dim, italic, underline and reverse, a color (black, red, green,
+// yellow, blue, magenta, cyan or white, optionally bright-), and a
+// background as on-<color>. An empty style or "none" leaves text as it is.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Style(String);
+
+const COLORS: &[&str] = &["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+impl Style {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn paint(&self, text: &str) -> String {
+        if self.is_empty() || text.is_empty() {
+            text.to_string()
+        } else {
+            format!("\x1b[{}m{}\x1b[0m", self.0, text)
+        }
+    }
+}
+
+impl FromStr for Style {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let color = |name: &str| COLORS.iter().position(|&color| color == name);
+        let mut codes = Vec::new();
+        for word in s.to_lowercase().split_whitespace() {
+            let code = match word {
+                "none" => continue,
+                "bold" => 1,
+                "dim" => 2,
+                "italic" => 3,
+                "underline" => 4,
+                "reverse" => 7,
+                _ => {
+                    let found = if let Some(name) = word.strip_prefix("bright-") {
+                        color(name).map(|n| 90 + n)
+                    } else if let Some(name) = word.strip_prefix("on-") {
+                        color(name).map(|n| 40 + n)
+                    } else {
+                        color(word).map(|n| 30 + n)
+                    };
+                    found.ok_or_else(|| {
+                        Error::new(
+                            ErrorKind::InvalidInput,
+                            format!("Invalid style '{}' (unknown word '{}')", s, word),
+                        )
+                    })?
+                }
+            };
+            codes.push(code.to_string());
+        }
+        Ok(Style(codes.join(";")))
+    }
+}
+
+impl<'de> Deserialize<'de> for Style {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+// Styles for the parts of a listing, defined in the config under
+// `[themes.<name>]`. Missing entries keep the default theme's style.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub header: Style,
+    // Open tasks past their deadline.
+    pub overdue: Style,
+    // Open tasks due later today.
+    pub due_today: Style,
+    // Done and cancelled tasks.
+    pub completed: Style,
+    pub in_progress: Style,
+    // Blocked tasks and those waiting on prerequisites.
+    pub blocked: Style,
+    // High and critical priorities.
+    pub high_priority: Style,
+    pub tags: Style,
+    // Search matches.
+    pub highlight: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            header: style("bold underline"),
+            overdue: style("red"),
+            due_today: style("yellow"),
+            completed: style("dim"),
+            in_progress: style("cyan"),
+            blocked: style("magenta"),
+            high_priority: style("bold"),
+            tags: style("blue"),
+            highlight: style("bold underline"),
+        }
+    }
+}
+
+impl Theme {
+    // The theme called `name`: one from the config, or the built-in
+    // `default` or `mono`, which only uses bold, dim and underline.
+    pub fn named(name: &str, themes: &BTreeMap<String, Theme>) -> Option<Theme> {
+        if let Some(theme) = themes.get(name) {
+            return Some(theme.clone());
+        }
+        match name {
+            "default" => Some(Theme::default()),
+            "mono" => Some(Theme {
+                overdue: style("bold"),
+                due_today: Style::default(),
+                in_progress: Style::default(),
+                blocked: Style::default(),
+                tags: Style::default(),
+                ..Theme::default()
+            }),
+            _ => None,
+        }
+    }
+
+    // The style for a whole task line: closed tasks are dimmed and open ones
+    // colored by how soon they are due.
+    pub fn task(&self, task: &Task, now: DateTime<Local>) -> &Style {
+        if !task.status.is_open() {
+            return &self.completed;
+        }
+        match task.due {
+            Some(due) if due.is_overdue(now) => &self.overdue,
+            Some(due) if due.local_date() == now.date_naive() => &self.due_today,
+            _ => &NO_STYLE,
+        }
+    }
+
+    pub fn status(&self, status: Status, waiting: bool) -> &Style {
+        match status {
+            Status::InProgress if !waiting => &self.in_progress,
+            Status::Blocked => &self.blocked,
+            status if waiting && status.is_open() => &self.blocked,
+            _ => &NO_STYLE,
+        }
+    }
+
+    pub fn priority(&self, priority: Priority) -> &Style {
+        if priority >= Priority::High {
+            &self.high_priority
+        } else {
+            &NO_STYLE
+        }
+    }
+}
+
+static NO_STYLE: Style = Style(String::new());
+
+// A built-in style, known to parse.
+fn style(s: &str) -> Style {
+    s.parse().unwrap_or_default()
+}
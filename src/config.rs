@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::color::{ColorChoice, Theme};
+use crate::history;
+use crate::report::Report;
+use crate::table::{self, Column, Layout, Overflow};
+use crate::tree::ChildPolicy;
+
+const APP_DIR: &str = "cli-todo";
+const CONFIG_FILE: &str = "config.toml";
+const DEFAULT_DATA_FILE: &str = "todo.json";
+const DEFAULT_DATABASE_FILE: &str = "todo.db";
+const DEFAULT_TRASH_RETENTION_DAYS: u32 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Json,
+    Sqlite,
+}
+
+impl Backend {
+    fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("db" | "sqlite" | "sqlite3") => Backend::Sqlite,
+            _ => Backend::Json,
+        }
+    }
+
+    fn default_file(&self) -> &'static str {
+        match self {
+            Backend::Json => DEFAULT_DATA_FILE,
+            Backend::Sqlite => DEFAULT_DATABASE_FILE,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // Relative paths are taken from the config file's directory.
+    pub file: Option<PathBuf>,
+    pub backend: Option<Backend>,
+    // Number of changes kept for undo; 0 turns the history off.
+    pub history_depth: usize,
+    // Trashed tasks older than this are purged on startup; 0 keeps them forever.
+    pub trash_retention_days: u32,
+    // Done and cancelled tasks closed longer ago than this are archived on
+    // startup; 0 leaves archiving to the `archive` command.
+    pub archive_after_days: u32,
+    // What closing or deleting a task does to its subtasks.
+    pub child_policy: ChildPolicy,
+    // Named reports for `cli-todo report`, by name.
+    pub reports: BTreeMap<String, Report>,
+    // Columns of the task table shown on a terminal.
+    pub columns: Vec<Column>,
+    // Whether long descriptions wrap or are truncated to the terminal width.
+    pub overflow: Overflow,
+    // When to color listings; `--color` overrides it.
+    pub color: ColorChoice,
+    // The theme to color with: `default`, `mono` or one from `themes`.
+    pub theme: String,
+    pub themes: BTreeMap<String, Theme>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            file: None,
+            backend: None,
+            history_depth: history::DEFAULT_DEPTH,
+            trash_retention_days: DEFAULT_TRASH_RETENTION_DAYS,
+            archive_after_days: 0,
+            child_policy: ChildPolicy::default(),
+            reports: BTreeMap::new(),
+            columns: table::DEFAULT_COLUMNS.to_vec(),
+            overflow: Overflow::default(),
+            color: ColorChoice::default(),
+            theme: "default".to_string(),
+            themes: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn layout(&self) -> Layout {
+        let theme = self
+            .color
+            .enabled()
+            .then(|| Theme::named(&self.theme, &self.themes).unwrap_or_default());
+        Layout::new(self.columns.clone(), self.overflow, theme)
+    }
+
+    pub fn load() -> Result<Self, io::Error> {
+        match config_path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Config::default()),
+        }
+    }
+
+    fn load_from(path: &Path) -> Result<Self, io::Error> {
+        let invalid = |message: String| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid config file {}: {}", path.display(), message),
+            )
+        };
+        match fs::read_to_string(path) {
+            Ok(contents) => {
+                let mut config: Config = toml::from_str(&contents).map_err(|e| invalid(e.to_string()))?;
+                if Theme::named(&config.theme, &config.themes).is_none() {
+                    return Err(invalid(format!("unknown theme '{}'", config.theme)));
+                }
+                // One configured list wherever the program is run from.
+                config.file = config.file.map(|file| match (expand_home(file), path.parent()) {
+                    (file, Some(dir)) if file.is_relative() => dir.join(file),
+                    (file, _) => file,
+                });
+                Ok(config)
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // The overrides come from `--file`/`TODO_FILE` and `--backend`/`TODO_BACKEND`,
+    // which take precedence over the config file and the XDG default. Without
+    // an explicit backend it is inferred from the file extension. A relative
+    // `--file` or `TODO_FILE` is taken from the current directory.
+    pub fn storage_location(
+        &self,
+        override_file: Option<PathBuf>,
+        override_backend: Option<Backend>,
+    ) -> Result<(Backend, PathBuf), io::Error> {
+        let backend = override_backend.or(self.backend);
+        if let Some(file) = override_file.or_else(|| self.file.clone()) {
+            let file = expand_home(file);
+            return Ok((backend.unwrap_or_else(|| Backend::for_path(&file)), file));
+        }
+        let backend = backend.unwrap_or(Backend::Json);
+        dirs::data_dir()
+            .map(|dir| (backend, dir.join(APP_DIR).join(backend.default_file())))
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::NotFound,
+                    "Could not determine the data directory; use --file or TODO_FILE",
+                )
+            })
+    }
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_DIR).join(CONFIG_FILE))
+}
+
+fn expand_home(path: PathBuf) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path,
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::{self, Error, ErrorKind};
+
+use crate::Task;
+
+// Checks that `id` can depend on each of `prerequisites`: they have to be
+// live tasks, and none of them may already depend on `id`, directly or not.
+// Links `id` already has are not checked again, so those left pointing at
+// trashed or archived tasks do not hold up later changes.
+pub fn check(tasks: &HashMap<usize, Task>, id: usize, prerequisites: &BTreeSet<usize>) -> Result<(), io::Error> {
+    let existing = tasks.get(&id).map(|task| &task.depends_on);
+    let added = prerequisites
+        .iter()
+        .copied()
+        .filter(|prerequisite| existing.is_none_or(|existing| !existing.contains(prerequisite)));
+    for prerequisite in added {
+        if tasks.get(&prerequisite).is_none_or(|task| task.is_trashed()) {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("Prerequisite task {} not found", prerequisite),
+            ));
+        }
+        if prerequisite == id {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Task {} cannot depend on itself", id),
+            ));
+        }
+        if depends_on(tasks, prerequisite, id) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Task {} cannot depend on task {}, which already depends on it",
+                    id, prerequisite
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Whether `id` is `target` or needs it done first, following links transitively.
+fn depends_on(tasks: &HashMap<usize, Task>, id: usize, target: usize) -> bool {
+    let mut pending = vec![id];
+    let mut seen = HashSet::new();
+    while let Some(current) = pending.pop() {
+        if current == target {
+            return true;
+        }
+        if seen.insert(current) {
+            if let Some(task) = tasks.get(&current) {
+                pending.extend(&task.depends_on);
+            }
+        }
+    }
+    false
+}
+
+// The prerequisites of `task` that are still open. Links to tasks that no
+// longer exist, such as purged or archived ones, do not hold it up.
+pub fn waiting_on(tasks: &HashMap<usize, Task>, task: &Task) -> Vec<usize> {
+    task.depends_on
+        .iter()
+        .copied()
+        .filter(|id| {
+            tasks
+                .get(id)
+                .is_some_and(|prerequisite| !prerequisite.is_trashed() && prerequisite.status.is_open())
+        })
+        .collect()
+}
+
+pub fn format_ids(ids: &[usize]) -> String {
+    let ids: Vec<String> = ids.iter().map(usize::to_string).collect();
+    ids.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: usize, depends_on: &[usize]) -> Task {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "description": format!("Task {}", id),
+            "status": "todo",
+            "depends_on": depends_on,
+        }))
+        .unwrap()
+    }
+
+    fn tasks(tasks: Vec<Task>) -> HashMap<usize, Task> {
+        tasks.into_iter().map(|task| (task.id.0, task)).collect()
+    }
+
+    fn ids(ids: &[usize]) -> BTreeSet<usize> {
+        ids.iter().copied().collect()
+    }
+
+    #[test]
+    fn a_task_cannot_depend_on_itself() {
+        let tasks = tasks(vec![task(1, &[])]);
+        let error = check(&tasks, 1, &ids(&[1])).unwrap_err();
+        assert_eq!(error.to_string(), "Task 1 cannot depend on itself");
+    }
+
+    #[test]
+    fn transitive_cycles_are_refused() {
+        // 3 waits on 2, which waits on 1.
+        let tasks = tasks(vec![task(1, &[]), task(2, &[1]), task(3, &[2])]);
+        let error = check(&tasks, 1, &ids(&[3])).unwrap_err();
+        assert_eq!(error.to_string(), "Task 1 cannot depend on task 3, which already depends on it");
+        assert!(check(&tasks, 3, &ids(&[1])).is_ok());
+    }
+
+    #[test]
+    fn prerequisites_have_to_be_live() {
+        let mut trashed = task(2, &[]);
+        trashed.deleted_at = Some(chrono::Utc::now());
+        let tasks = tasks(vec![task(1, &[]), trashed]);
+        for missing in [2, 9] {
+            let error = check(&tasks, 1, &ids(&[missing])).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::NotFound);
+        }
+    }
+
+    #[test]
+    fn existing_dangling_links_are_not_checked_again() {
+        // Task 1 still links to 5, which has been archived, and to 2, which
+        // is in the trash.
+        let mut trashed = task(2, &[]);
+        trashed.deleted_at = Some(chrono::Utc::now());
+        let tasks = tasks(vec![task(1, &[2, 5]), trashed, task(3, &[])]);
+        assert!(check(&tasks, 1, &ids(&[2, 3, 5])).is_ok());
+        assert!(check(&tasks, 1, &ids(&[5])).is_ok());
+        assert!(check(&tasks, 1, &ids(&[2, 4, 5])).is_err());
+        assert!(waiting_on(&tasks, &tasks[&1]).is_empty());
+    }
+}
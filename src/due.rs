@@ -0,0 +1,213 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::io::{self, Error, ErrorKind};
+
+use chrono::{
+    DateTime, Datelike, Days, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta,
+    TimeZone, Weekday,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Due {
+    Date(NaiveDate),
+    DateTime(DateTime<FixedOffset>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DueView {
+    Overdue,
+    Today,
+    Week,
+}
+
+impl Due {
+    // Accepts `today`, `tomorrow`, `yesterday`, weekday names (the next such day,
+    // today included), offsets like `+3d`/`+2w`, `YYYY-MM-DD`, a local
+    // `YYYY-MM-DD HH:MM` and RFC 3339 date-times with an explicit offset.
+    pub fn parse(input: &str, now: DateTime<Local>) -> Result<Self, io::Error> {
+        let input = input.trim();
+        let today = now.date_naive();
+        let lower = input.to_lowercase();
+
+        let relative = match lower.as_str() {
+            "today" => Some(today),
+            "tomorrow" => today.checked_add_days(Days::new(1)),
+            "yesterday" => today.checked_sub_days(Days::new(1)),
+            _ => None,
+        };
+        if let Some(date) = relative {
+            return Ok(Due::Date(date));
+        }
+        if let Ok(weekday) = lower.parse::<Weekday>() {
+            let ahead = (7 + weekday.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+            return Ok(Due::Date(today + Days::new(ahead.into())));
+        }
+        if let Some(offset) = lower.strip_prefix('+') {
+            if let Some(date) = parse_offset(offset).and_then(|days| today.checked_add_days(days)) {
+                return Ok(Due::Date(date));
+            }
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(input, DATE_FORMAT) {
+            return Ok(Due::Date(date));
+        }
+        if let Ok(datetime) = DateTime::parse_from_rfc3339(input) {
+            return Ok(Due::DateTime(datetime));
+        }
+        for format in ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"] {
+            if let Ok(naive) = NaiveDateTime::parse_from_str(input, format) {
+                if let Some(local) = Local.from_local_datetime(&naive).earliest() {
+                    return Ok(Due::DateTime(local.fixed_offset()));
+                }
+            }
+        }
+
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid due date '{}'", input),
+        ))
+    }
+
+    // Date-only deadlines count as due until the end of that local day.
+    pub fn deadline(&self) -> DateTime<Local> {
+        match self {
+            Due::Date(date) => {
+                let end_of_day = date.and_time(NaiveTime::from_hms_opt(23, 59, 59).unwrap());
+                Local
+                    .from_local_datetime(&end_of_day)
+                    .latest()
+                    .unwrap_or_else(|| Local.from_utc_datetime(&end_of_day))
+            }
+            Due::DateTime(datetime) => datetime.with_timezone(&Local),
+        }
+    }
+
+    pub fn local_date(&self) -> NaiveDate {
+        match self {
+            Due::Date(date) => *date,
+            Due::DateTime(datetime) => datetime.with_timezone(&Local).date_naive(),
+        }
+    }
+
+    // The same deadline moved to another local date, keeping any time of day.
+    pub fn with_date(&self, date: NaiveDate) -> Due {
+        match self {
+            Due::Date(_) => Due::Date(date),
+            Due::DateTime(datetime) => {
+                let shift = TimeDelta::days((date - self.local_date()).num_days());
+                Due::DateTime(*datetime + shift)
+            }
+        }
+    }
+
+    pub fn is_overdue(&self, now: DateTime<Local>) -> bool {
+        self.deadline() < now
+    }
+
+    // The stored form: `YYYY-MM-DD` for dates, RFC 3339 for date-times.
+    pub fn encode(&self) -> String {
+        match self {
+            Due::Date(date) => date.format(DATE_FORMAT).to_string(),
+            Due::DateTime(datetime) => datetime.to_rfc3339(),
+        }
+    }
+
+    pub fn decode(value: &str) -> Result<Self, chrono::ParseError> {
+        match NaiveDate::parse_from_str(value, DATE_FORMAT) {
+            Ok(date) => Ok(Due::Date(date)),
+            Err(_) => DateTime::parse_from_rfc3339(value).map(Due::DateTime),
+        }
+    }
+}
+
+// By deadline. A date and a date-time at the end of that day share a
+// deadline but are not equal, so the date sorts first to keep the order
+// consistent with `==`.
+impl Ord for Due {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let kind = |due: &Due| matches!(due, Due::DateTime(_));
+        self.deadline()
+            .cmp(&other.deadline())
+            .then_with(|| kind(self).cmp(&kind(other)))
+    }
+}
+
+impl PartialOrd for Due {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl DueView {
+    pub fn matches(&self, due: &Due, now: DateTime<Local>) -> bool {
+        let today = now.date_naive();
+        match self {
+            DueView::Overdue => due.is_overdue(now),
+            DueView::Today => !due.is_overdue(now) && due.local_date() == today,
+            DueView::Week => {
+                let days_left = 6 - today.weekday().num_days_from_monday();
+                let end_of_week = today + Days::new(days_left.into());
+                !due.is_overdue(now) && due.local_date() <= end_of_week
+            }
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            DueView::Overdue => "Overdue",
+            DueView::Today => "Due today",
+            DueView::Week => "Due this week",
+        }
+    }
+}
+
+fn parse_offset(offset: &str) -> Option<Days> {
+    let (count, unit) = offset.split_at(offset.len().checked_sub(1)?);
+    let count: u64 = count.parse().ok()?;
+    match unit {
+        "d" => Some(Days::new(count)),
+        "w" => Some(Days::new(count.checked_mul(7)?)),
+        _ => None,
+    }
+}
+
+impl fmt::Display for Due {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Due::Date(date) => write!(f, "{}", date.format(DATE_FORMAT)),
+            Due::DateTime(datetime) => write!(f, "{}", datetime.format("%Y-%m-%d %H:%M %:z")),
+        }
+    }
+}
+
+impl Serialize for Due {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.encode())
+    }
+}
+
+impl<'de> Deserialize<'de> for Due {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Due::decode(&value).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn order_agrees_with_equality() {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let end_of_day = Due::Date(date).deadline().fixed_offset();
+        let (a, b) = (Due::Date(date), Due::DateTime(end_of_day));
+        assert_eq!(a.deadline(), b.deadline());
+        assert_ne!(a, b);
+        assert_eq!(a.cmp(&b), Ordering::Less);
+        assert_eq!(b.cmp(&a), Ordering::Greater);
+        assert_eq!(a.cmp(&a), Ordering::Equal);
+    }
+}
@@ -0,0 +1,92 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Error, ErrorKind, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::Task;
+
+const HELP: &str = "\
+# The first line is the task description; anything after a blank line is
+# kept as notes. Lines starting with '#' are ignored. Save an empty
+# description to abort the edit.";
+
+// Opens `$VISUAL`/`$EDITOR` (falling back to `vi`) on the task's description
+// and notes and returns the edited pair, or `None` if the description was
+// left empty.
+pub fn edit_task_text(task: &Task) -> Result<Option<(String, Option<String>)>, io::Error> {
+    let mut text = format!("{}\n", task.description.get());
+    if let Some(notes) = &task.notes {
+        text.push_str(&format!("\n{}\n", notes));
+    }
+    text.push_str(&format!("\n{}\n", HELP));
+
+    let path = create_temp_file(task.id.0, &text)?;
+    let result = run_editor(&path.to_string_lossy()).and_then(|_| fs::read_to_string(&path));
+    let _ = fs::remove_file(&path);
+
+    Ok(parse(&result?))
+}
+
+// Writes `text` to a new file in the temp directory that only the current
+// user can read. The file must not exist beforehand, so a name planted by
+// someone else (such as a symlink in a shared /tmp) is never written through;
+// another name is tried instead.
+fn create_temp_file(id: usize, text: &str) -> Result<PathBuf, io::Error> {
+    const ATTEMPTS: u32 = 16;
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.subsec_nanos());
+    for attempt in 0..ATTEMPTS {
+        let name = format!("cli-todo-{}-{}-{:08x}.txt", std::process::id(), id, nanos.wrapping_add(attempt));
+        let path = env::temp_dir().join(name);
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        match options.open(&path) {
+            Ok(mut file) => {
+                if let Err(e) = file.write_all(text.as_bytes()) {
+                    let _ = fs::remove_file(&path);
+                    return Err(e);
+                }
+                return Ok(path);
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(Error::new(
+        ErrorKind::AlreadyExists,
+        "Could not create a temporary file for the editor",
+    ))
+}
+
+fn run_editor(path: &str) -> Result<(), io::Error> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // Run through the shell so editors configured with arguments ("code -w") work.
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(path)
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::other(format!("Editor '{}' exited with {}", editor, status)))
+    }
+}
+
+fn parse(text: &str) -> Option<(String, Option<String>)> {
+    let lines: Vec<&str> = text.lines().filter(|line| !line.starts_with('#')).collect();
+    let start = lines.iter().position(|line| !line.trim().is_empty())?;
+    let description = lines[start].trim().to_string();
+    let notes = lines[start + 1..].join("\n").trim().to_string();
+    Some((description, (!notes.is_empty()).then_some(notes)))
+}
@@ -0,0 +1,558 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::io::{self, Error, ErrorKind};
+use std::ops::Range;
+
+use chrono::{DateTime, Local, Utc};
+
+use crate::age;
+use crate::due::Due;
+use crate::priority::Priority;
+use crate::status::Status;
+use crate::Task;
+
+// A parsed filter expression such as
+// `status:open and (tag:work or priority>=high) and due<friday`.
+//
+// Terms are `field<op>value`, with `:` or `=` for equality and `!=`, `<`,
+// `<=`, `>`, `>=` where the field is ordered. They combine with `and`, `or`,
+// `not` and parentheses; `and` binds tighter than `or` and may be left out
+// between terms. A bare word matches descriptions containing it, and values
+// with spaces go in double quotes. Dates take anything `Due::parse` accepts
+// plus `now`, and timestamps anything `age::parse_cutoff` accepts.
+// `is:actionable` matches the tasks `next` lists.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Term(Term),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Status(Op, StatusGroup),
+    Tag(Op, String),
+    Priority(Op, Priority),
+    // `None` stands for "no due date".
+    Due(Op, Option<Due>),
+    Created(Op, DateTime<Utc>),
+    Modified(Op, DateTime<Utc>),
+    Completed(Op, DateTime<Utc>),
+    Id(Op, usize),
+    Parent(Op, Option<usize>),
+    // Lowercased text found in the description, or with `notes` also in the notes.
+    Text { text: String, notes: bool },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusGroup {
+    Open,
+    Closed,
+    // Open tasks that are not blocked, waiting or holding open subtasks.
+    Actionable,
+    Is(Status),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn holds(&self, ordering: Ordering) -> bool {
+        match self {
+            Op::Eq => ordering == Ordering::Equal,
+            Op::Ne => ordering != Ordering::Equal,
+            Op::Lt => ordering == Ordering::Less,
+            Op::Le => ordering != Ordering::Greater,
+            Op::Gt => ordering == Ordering::Greater,
+            Op::Ge => ordering != Ordering::Less,
+        }
+    }
+
+    fn is_equality(&self) -> bool {
+        matches!(self, Op::Eq | Op::Ne)
+    }
+}
+
+impl Filter {
+    pub fn parse(input: &str) -> Result<Self, io::Error> {
+        Parser::new(input)?.parse()
+    }
+
+    pub fn matches(&self, task: &Task, context: &Context) -> bool {
+        match self {
+            Filter::And(left, right) => left.matches(task, context) && right.matches(task, context),
+            Filter::Or(left, right) => left.matches(task, context) || right.matches(task, context),
+            Filter::Not(filter) => !filter.matches(task, context),
+            Filter::Term(term) => term.matches(task, context),
+        }
+    }
+}
+
+// What matching needs to know beyond the task itself.
+pub struct Context {
+    pub now: DateTime<Local>,
+    // IDs of the tasks `is:actionable` matches.
+    actionable: HashSet<usize>,
+}
+
+impl Context {
+    pub fn new<'a>(now: DateTime<Local>, actionable: impl IntoIterator<Item = &'a Task>) -> Self {
+        Context {
+            now,
+            actionable: actionable.into_iter().map(|task| task.id.0).collect(),
+        }
+    }
+}
+
+impl Term {
+    fn matches(&self, task: &Task, context: &Context) -> bool {
+        let now = context.now;
+        match self {
+            Term::Status(op, group) => {
+                let is = match group {
+                    StatusGroup::Open => task.status.is_open(),
+                    StatusGroup::Closed => !task.status.is_open(),
+                    StatusGroup::Actionable => context.actionable.contains(&task.id.0),
+                    StatusGroup::Is(status) => task.status == *status,
+                };
+                is == (*op == Op::Eq)
+            }
+            Term::Tag(op, tag) => task.tags.contains(tag) == (*op == Op::Eq),
+            Term::Priority(op, priority) => op.holds(task.priority.cmp(priority)),
+            Term::Due(op, None) => task.due.is_none() == (*op == Op::Eq),
+            // Tasks without a due date only match `!=`.
+            Term::Due(op, Some(due)) => match task.due {
+                Some(task_due) if op.is_equality() => {
+                    op.holds(task_due.local_date().cmp(&due.local_date()))
+                }
+                Some(task_due) => op.holds(task_due.cmp(due)),
+                None => *op == Op::Ne,
+            },
+            Term::Created(op, at) => timestamp_matches(task.created_at, *op, *at, now),
+            Term::Modified(op, at) => timestamp_matches(task.modified_at, *op, *at, now),
+            Term::Completed(op, at) => timestamp_matches(task.completed_at, *op, *at, now),
+            Term::Id(op, id) => op.holds(task.id.0.cmp(id)),
+            Term::Parent(op, parent) => (task.parent == *parent) == (*op == Op::Eq),
+            Term::Text { text, notes } => {
+                task.description.get().to_lowercase().contains(text)
+                    || *notes && task.notes.as_deref().is_some_and(|n| n.to_lowercase().contains(text))
+            }
+        }
+    }
+}
+
+// Equality compares local calendar days, so `created:today` means "some
+// time today"; the other comparisons use the exact cutoff.
+fn timestamp_matches(timestamp: Option<DateTime<Utc>>, op: Op, at: DateTime<Utc>, now: DateTime<Local>) -> bool {
+    let Some(timestamp) = timestamp else {
+        return op == Op::Ne;
+    };
+    if op.is_equality() {
+        let day = |at: DateTime<Utc>| at.with_timezone(&now.timezone()).date_naive();
+        op.holds(day(timestamp).cmp(&day(at)))
+    } else {
+        op.holds(timestamp.cmp(&at))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    // A word with its quotes removed. Only the part before the first quote,
+    // `unquoted` bytes long, can hold the field and operator of a term.
+    Word { text: String, unquoted: usize },
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<(Token, Range<usize>)>,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Result<Self, io::Error> {
+        Ok(Parser {
+            input,
+            tokens: tokenize(input)?,
+            position: 0,
+        })
+    }
+
+    fn parse(mut self) -> Result<Filter, io::Error> {
+        if self.tokens.is_empty() {
+            return Err(parse_error(self.input, 0..0, "empty filter"));
+        }
+        let filter = self.parse_or()?;
+        match self.tokens.get(self.position) {
+            None => Ok(filter),
+            Some((Token::Close, span)) => Err(parse_error(self.input, span.clone(), "unmatched ')'")),
+            Some((_, span)) => Err(parse_error(self.input, span.clone(), "expected 'and' or 'or'")),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    // The span just past the input, for errors about something missing at the end.
+    fn end(&self) -> Range<usize> {
+        self.input.len()..self.input.len()
+    }
+
+    fn parse_or(&mut self) -> Result<Filter, io::Error> {
+        let mut filter = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            filter = Filter::Or(Box::new(filter), Box::new(self.parse_and()?));
+        }
+        Ok(filter)
+    }
+
+    fn parse_and(&mut self) -> Result<Filter, io::Error> {
+        let mut filter = self.parse_unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.position += 1,
+                Some(Token::Open | Token::Not | Token::Word { .. }) => {}
+                _ => return Ok(filter),
+            }
+            filter = Filter::And(Box::new(filter), Box::new(self.parse_unary()?));
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Filter, io::Error> {
+        let Some((token, span)) = self.tokens.get(self.position).cloned() else {
+            return Err(parse_error(self.input, self.end(), "expected a term"));
+        };
+        self.position += 1;
+        match token {
+            Token::Not => Ok(Filter::Not(Box::new(self.parse_unary()?))),
+            Token::Open => {
+                let filter = self.parse_or()?;
+                match self.tokens.get(self.position) {
+                    Some((Token::Close, _)) => {
+                        self.position += 1;
+                        Ok(filter)
+                    }
+                    Some((_, next)) => Err(parse_error(self.input, next.clone(), "expected ')'")),
+                    None => Err(parse_error(self.input, span, "unclosed '('")),
+                }
+            }
+            Token::Word { text, unquoted } => parse_term(self.input, &text, unquoted, span),
+            Token::Close | Token::And | Token::Or => Err(parse_error(
+                self.input,
+                span,
+                "expected a term",
+            )),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<(Token, Range<usize>)>, io::Error> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if c == '(' || c == ')' {
+            chars.next();
+            let token = if c == '(' { Token::Open } else { Token::Close };
+            tokens.push((token, start..start + 1));
+            continue;
+        }
+
+        let mut text = String::new();
+        let mut unquoted = None;
+        let mut quote_start = None;
+        let mut end = start;
+        while let Some(&(index, c)) = chars.peek() {
+            if quote_start.is_none() && (c.is_whitespace() || c == '(' || c == ')') {
+                break;
+            }
+            chars.next();
+            end = index + c.len_utf8();
+            if c == '"' {
+                unquoted.get_or_insert(text.len());
+                quote_start = match quote_start {
+                    Some(_) => None,
+                    None => Some(index),
+                };
+            } else {
+                text.push(c);
+            }
+        }
+        if let Some(quote) = quote_start {
+            return Err(parse_error(input, quote..quote + 1, "unclosed quote"));
+        }
+        let quoted = unquoted.is_some();
+        let unquoted = unquoted.unwrap_or(text.len());
+        let token = match text.to_lowercase().as_str() {
+            "and" if !quoted => Token::And,
+            "or" if !quoted => Token::Or,
+            "not" if !quoted => Token::Not,
+            _ => Token::Word { text, unquoted },
+        };
+        tokens.push((token, start..end));
+    }
+    Ok(tokens)
+}
+
+const OPERATORS: &[(&str, Op)] = &[
+    ("!=", Op::Ne),
+    ("<=", Op::Le),
+    (">=", Op::Ge),
+    (":", Op::Eq),
+    ("=", Op::Eq),
+    ("<", Op::Lt),
+    (">", Op::Gt),
+];
+
+// Splits a word into field, operator and value at the first operator, or
+// treats it as text to look for when it has none. `span` locates the word
+// in `input` for errors.
+fn parse_term(input: &str, word: &str, unquoted: usize, span: Range<usize>) -> Result<Filter, io::Error> {
+    let split = word[..unquoted].char_indices().find_map(|(index, _)| {
+        OPERATORS
+            .iter()
+            .find(|(symbol, _)| word[index..unquoted].starts_with(symbol))
+            .map(|&(symbol, op)| (index, symbol, op))
+    });
+    let Some((at, symbol, op)) = split.filter(|&(at, _, _)| at > 0) else {
+        return Ok(Filter::Term(Term::Text {
+            text: word.to_lowercase(),
+            notes: false,
+        }));
+    };
+    let field = word[..at].to_lowercase();
+    let value = &word[at + symbol.len()..];
+
+    // The field and operator come before any quotes, so their offsets in the
+    // word are offsets in the input too.
+    let field_span = span.start..span.start + at;
+    let op_span = field_span.end..field_span.end + symbol.len();
+    let value_span = op_span.end..span.end;
+    let equality_only = |op: Op| -> Result<(), io::Error> {
+        if op.is_equality() {
+            Ok(())
+        } else {
+            let message = format!("'{}' only supports ':' and '!='", field);
+            Err(parse_error(input, op_span.clone(), &message))
+        }
+    };
+
+    if value.is_empty() {
+        let message = format!("expected a value after '{}{}'", field, symbol);
+        return Err(parse_error(input, value_span, &message));
+    }
+    let invalid_value = |e: io::Error| parse_error(input, value_span.clone(), &e.to_string());
+    let now = Local::now();
+    let term = match field.as_str() {
+        "status" | "is" => {
+            equality_only(op)?;
+            let group = match value.to_lowercase().as_str() {
+                "open" | "pending" => StatusGroup::Open,
+                "closed" => StatusGroup::Closed,
+                "actionable" => StatusGroup::Actionable,
+                _ => StatusGroup::Is(value.parse().map_err(invalid_value)?),
+            };
+            Term::Status(op, group)
+        }
+        "tag" => {
+            equality_only(op)?;
+            let tag = value.strip_prefix('+').unwrap_or(value);
+            Term::Tag(op, tag.to_string())
+        }
+        "priority" | "pri" => Term::Priority(op, value.parse().map_err(invalid_value)?),
+        "due" => {
+            if value.eq_ignore_ascii_case("none") {
+                equality_only(op)?;
+                Term::Due(op, None)
+            } else if value.eq_ignore_ascii_case("any") {
+                equality_only(op)?;
+                Term::Due(if op == Op::Eq { Op::Ne } else { Op::Eq }, None)
+            } else if value.eq_ignore_ascii_case("now") {
+                Term::Due(op, Some(Due::DateTime(now.fixed_offset())))
+            } else {
+                Term::Due(op, Some(Due::parse(value, now).map_err(invalid_value)?))
+            }
+        }
+        "created" | "modified" | "completed" => {
+            let at = age::parse_cutoff(value, now).map_err(invalid_value)?;
+            match field.as_str() {
+                "created" => Term::Created(op, at),
+                "modified" => Term::Modified(op, at),
+                _ => Term::Completed(op, at),
+            }
+        }
+        "id" => Term::Id(op, parse_id(value).map_err(invalid_value)?),
+        "parent" => {
+            equality_only(op)?;
+            let parent = if value.eq_ignore_ascii_case("none") {
+                None
+            } else {
+                Some(parse_id(value).map_err(invalid_value)?)
+            };
+            Term::Parent(op, parent)
+        }
+        "description" | "desc" | "text" => {
+            equality_only(op)?;
+            let term = Filter::Term(Term::Text {
+                text: value.to_lowercase(),
+                notes: field == "text",
+            });
+            // Text has no inequality of its own, so `!=` reads as "does not contain".
+            return Ok(if op == Op::Eq { term } else { Filter::Not(Box::new(term)) });
+        }
+        _ => {
+            let message = format!(
+                "unknown field '{}' (expected status, tag, priority, due, created, modified, completed, id, parent, description or text)",
+                field
+            );
+            return Err(parse_error(input, field_span, &message));
+        }
+    };
+    Ok(Filter::Term(term))
+}
+
+fn parse_id(value: &str) -> Result<usize, io::Error> {
+    value
+        .parse()
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, format!("Invalid task ID '{}'", value)))
+}
+
+// Reports a parse error with the input underneath and a marker under the
+// offending part.
+fn parse_error(input: &str, span: Range<usize>, message: &str) -> io::Error {
+    let column = input[..span.start].chars().count();
+    let width = input[span.clone()].chars().count().max(1);
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "Invalid filter at column {}: {}\n  {}\n  {}{}",
+            column + 1,
+            message,
+            input,
+            " ".repeat(column),
+            "^".repeat(width)
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(name: &str) -> Filter {
+        Filter::Term(Term::Tag(Op::Eq, name.to_string()))
+    }
+
+    fn and(left: Filter, right: Filter) -> Filter {
+        Filter::And(Box::new(left), Box::new(right))
+    }
+
+    fn or(left: Filter, right: Filter) -> Filter {
+        Filter::Or(Box::new(left), Box::new(right))
+    }
+
+    fn text(text: &str) -> Filter {
+        Filter::Term(Term::Text {
+            text: text.to_string(),
+            notes: false,
+        })
+    }
+
+    fn error(input: &str) -> String {
+        Filter::parse(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let parsed = Filter::parse("tag:a or tag:b and tag:c").unwrap();
+        assert_eq!(parsed, or(tag("a"), and(tag("b"), tag("c"))));
+        let parsed = Filter::parse("tag:a and tag:b or tag:c").unwrap();
+        assert_eq!(parsed, or(and(tag("a"), tag("b")), tag("c")));
+    }
+
+    #[test]
+    fn terms_side_by_side_are_anded() {
+        let parsed = Filter::parse("tag:a tag:b or tag:c").unwrap();
+        assert_eq!(parsed, or(and(tag("a"), tag("b")), tag("c")));
+    }
+
+    #[test]
+    fn parentheses_and_not_group() {
+        let parsed = Filter::parse("(tag:a or tag:b) tag:c").unwrap();
+        assert_eq!(parsed, and(or(tag("a"), tag("b")), tag("c")));
+        let parsed = Filter::parse("not tag:a and tag:b").unwrap();
+        assert_eq!(parsed, and(Filter::Not(Box::new(tag("a"))), tag("b")));
+    }
+
+    #[test]
+    fn quotes_keep_spaces_and_keywords_in_values() {
+        assert_eq!(Filter::parse("tag:\"x or y\"").unwrap(), tag("x or y"));
+        assert_eq!(Filter::parse("desc:\"Buy Milk\"").unwrap(), text("buy milk"));
+        assert_eq!(Filter::parse("\"or\"").unwrap(), text("or"));
+    }
+
+    #[test]
+    fn quoted_operators_are_text() {
+        assert_eq!(Filter::parse("\"status:done\"").unwrap(), text("status:done"));
+        assert_eq!(Filter::parse("\"a<b\"").unwrap(), text("a<b"));
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_part() {
+        assert_eq!(
+            error("foo:bar"),
+            "Invalid filter at column 1: unknown field 'foo' (expected status, tag, priority, due, \
+             created, modified, completed, id, parent, description or text)\n  foo:bar\n  ^^^"
+        );
+        assert_eq!(
+            error("tag:a and"),
+            "Invalid filter at column 10: expected a term\n  tag:a and\n           ^"
+        );
+        assert_eq!(
+            error("(tag:a or tag:b"),
+            "Invalid filter at column 1: unclosed '('\n  (tag:a or tag:b\n  ^"
+        );
+        assert_eq!(
+            error("tag:a )"),
+            "Invalid filter at column 7: unmatched ')'\n  tag:a )\n        ^"
+        );
+        assert_eq!(
+            error("tag:\"work"),
+            "Invalid filter at column 5: unclosed quote\n  tag:\"work\n      ^"
+        );
+        assert_eq!(
+            error("priority>=urgent"),
+            "Invalid filter at column 11: Invalid priority 'urgent' (expected none, low, medium, high or \
+             critical)\n  priority>=urgent\n            ^^^^^^"
+        );
+    }
+
+    #[test]
+    fn actionable_comes_from_the_context() {
+        let task = |id: usize| -> Task {
+            serde_json::from_value(serde_json::json!({ "id": id, "description": "Task", "status": "todo" })).unwrap()
+        };
+        let (first, second) = (task(1), task(2));
+        let context = Context::new(Local::now(), [&first]);
+        let filter = Filter::parse("is:actionable").unwrap();
+        assert!(filter.matches(&first, &context));
+        assert!(!filter.matches(&second, &context));
+    }
+}
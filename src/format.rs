@@ -0,0 +1,208 @@
+use std::collections::BTreeSet;
+use std::io::{self, Error, ErrorKind, Write};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::due::Due;
+use crate::priority::Priority;
+use crate::recur::Recurrence;
+use crate::status::Status;
+use crate::Task;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    // The usual task lines.
+    #[default]
+    Text,
+    // A JSON array of records, or a single object for one task.
+    Json,
+    // One JSON record per line.
+    Jsonl,
+    Csv,
+    Tsv,
+}
+
+// The `--format` long help, documenting `Record`; keep the two in step.
+pub const LONG_HELP: &str = "\
+Output format. text gives the usual listing; json, jsonl, csv and tsv give
+every field of each task. JSON is an array of records (a single object for
+show) and JSONL one record per line. Every record has all of these fields,
+in this order, with the same encodings as the todo file:
+
+  id            number
+  description   string
+  status        \"todo\", \"in-progress\", \"blocked\", \"done\" or \"cancelled\"
+  priority      \"none\", \"low\", \"medium\", \"high\" or \"critical\"
+  due           \"YYYY-MM-DD\", an RFC 3339 date-time, or null
+  tags          array of strings
+  notes         string or null
+  parent        number or null
+  depends_on    array of numbers
+  recurrence    rule such as \"weekly:mon,thu\", or null
+  series        number or null
+  created_at, modified_at, completed_at, deleted_at
+                RFC 3339 UTC timestamps, or null
+
+CSV and TSV start with a header row of these names; arrays are joined with
+spaces and nulls are empty. CSV quotes fields as in RFC 4180, and TSV
+escapes tabs, line breaks and backslashes as \\t, \\n, \\r and \\\\.";
+
+// The machine-readable form of a task, as described in `LONG_HELP`.
+#[derive(Serialize)]
+struct Record<'a> {
+    id: usize,
+    description: &'a str,
+    status: Status,
+    priority: Priority,
+    due: Option<Due>,
+    tags: &'a BTreeSet<String>,
+    notes: Option<&'a str>,
+    parent: Option<usize>,
+    depends_on: &'a BTreeSet<usize>,
+    recurrence: Option<&'a Recurrence>,
+    series: Option<usize>,
+    created_at: Option<DateTime<Utc>>,
+    modified_at: Option<DateTime<Utc>>,
+    completed_at: Option<DateTime<Utc>>,
+    deleted_at: Option<DateTime<Utc>>,
+}
+
+const FIELDS: &[&str] = &[
+    "id",
+    "description",
+    "status",
+    "priority",
+    "due",
+    "tags",
+    "notes",
+    "parent",
+    "depends_on",
+    "recurrence",
+    "series",
+    "created_at",
+    "modified_at",
+    "completed_at",
+    "deleted_at",
+];
+
+impl<'a> Record<'a> {
+    fn new(task: &'a Task) -> Self {
+        Record {
+            id: task.id.0,
+            description: task.description.get(),
+            status: task.status,
+            priority: task.priority,
+            due: task.due,
+            tags: &task.tags,
+            notes: task.notes.as_deref(),
+            parent: task.parent,
+            depends_on: &task.depends_on,
+            recurrence: task.recurrence.as_ref(),
+            series: task.series,
+            created_at: task.created_at,
+            modified_at: task.modified_at,
+            completed_at: task.completed_at,
+            deleted_at: task.deleted_at,
+        }
+    }
+
+    // The field values in `FIELDS` order as plain text, for CSV and TSV.
+    fn cells(&self) -> Result<Vec<String>, io::Error> {
+        let serde_json::Value::Object(mut fields) = serde_json::to_value(self).map_err(json_error)? else {
+            return Err(Error::other("Task record is not an object"));
+        };
+        let cell = |value: serde_json::Value| match value {
+            serde_json::Value::Null => String::new(),
+            serde_json::Value::String(text) => text,
+            serde_json::Value::Array(items) => {
+                let items: Vec<String> = items
+                    .into_iter()
+                    .map(|item| match item {
+                        serde_json::Value::String(text) => text,
+                        item => item.to_string(),
+                    })
+                    .collect();
+                items.join(" ")
+            }
+            value => value.to_string(),
+        };
+        Ok(FIELDS
+            .iter()
+            .map(|field| cell(fields.remove(*field).unwrap_or_default()))
+            .collect())
+    }
+}
+
+fn json_error(e: serde_json::Error) -> io::Error {
+    Error::new(ErrorKind::InvalidData, e)
+}
+
+// Prints `tasks` as a list in `format`, in the order given.
+pub fn print_tasks(tasks: &[&Task], format: Format) -> Result<(), io::Error> {
+    let records: Vec<Record> = tasks.iter().map(|task| Record::new(task)).collect();
+    let mut out = io::stdout().lock();
+    let written = match format {
+        Format::Text => {
+            tasks.iter().for_each(|task| crate::print_task(task));
+            Ok(())
+        }
+        Format::Json => {
+            let json = serde_json::to_string_pretty(&records).map_err(json_error)?;
+            writeln!(out, "{}", json)
+        }
+        Format::Jsonl => records.iter().try_for_each(|record| {
+            let json = serde_json::to_string(record).map_err(json_error)?;
+            writeln!(out, "{}", json)
+        }),
+        Format::Csv | Format::Tsv => {
+            let escape = if format == Format::Csv { csv_field } else { tsv_field };
+            let separator = if format == Format::Csv { "," } else { "\t" };
+            writeln!(out, "{}", FIELDS.join(separator)).and_then(|()| {
+                records.iter().try_for_each(|record| {
+                    let cells: Vec<String> = record.cells()?.iter().map(|cell| escape(cell)).collect();
+                    writeln!(out, "{}", cells.join(separator))
+                })
+            })
+        }
+    };
+    closed_pipe_is_ok(written.and_then(|()| out.flush()))
+}
+
+// Prints one task in `format`; JSON gives a bare object rather than an array.
+pub fn print_task(task: &Task, format: Format) -> Result<(), io::Error> {
+    match format {
+        Format::Json => {
+            let json = serde_json::to_string_pretty(&Record::new(task)).map_err(json_error)?;
+            closed_pipe_is_ok(writeln!(io::stdout().lock(), "{}", json))
+        }
+        format => print_tasks(&[task], format),
+    }
+}
+
+// Output piped into something like `head` may stop being read part-way;
+// that is the reader's choice, not an error.
+fn closed_pipe_is_ok(result: Result<(), io::Error>) -> Result<(), io::Error> {
+    match result {
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+// Quotes fields containing separators, quotes or line breaks (RFC 4180).
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// TSV fields cannot contain tabs or line breaks, so those are escaped.
+fn tsv_field(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
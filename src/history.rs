@@ -0,0 +1,196 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{write_atomically, StorageOp, Task};
+
+pub const DEFAULT_DEPTH: usize = 100;
+
+// One task's state in the list before and after a change; `None` means it
+// did not exist, or was in the archive when `archived` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    pub id: usize,
+    pub before: Option<Task>,
+    pub after: Option<Task>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub archived: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub at: DateTime<Utc>,
+    pub changes: Vec<Change>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Journal {
+    #[serde(default)]
+    pub undo: Vec<Entry>,
+    #[serde(default)]
+    pub redo: Vec<Entry>,
+}
+
+// The undo/redo journal, stored next to the todo file so it survives
+// between sessions. A depth of 0 disables recording.
+pub struct History {
+    path: PathBuf,
+    depth: usize,
+}
+
+impl Change {
+    fn summary(&self) -> String {
+        match (&self.before, &self.after) {
+            (None, _) if self.archived => format!("unarchive task {}", self.id),
+            (_, None) if self.archived => format!("archive task {}", self.id),
+            (None, _) => format!("add task {}", self.id),
+            (_, None) => format!("purge task {}", self.id),
+            (Some(before), Some(after)) if before.is_trashed() != after.is_trashed() => {
+                let verb = if after.is_trashed() { "delete" } else { "restore" };
+                format!("{} task {}", verb, self.id)
+            }
+            (Some(before), Some(after)) if before.status != after.status => {
+                format!("mark task {} {}", self.id, after.status)
+            }
+            _ => format!("edit task {}", self.id),
+        }
+    }
+}
+
+impl Entry {
+    pub fn new(changes: Vec<Change>) -> Self {
+        Entry {
+            at: Utc::now(),
+            changes,
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let summaries: Vec<String> = self.changes.iter().map(Change::summary).collect();
+        summaries.join(", ")
+    }
+
+    // The storage operations that restore every task to its `before` state,
+    // or to its `after` state when redoing.
+    pub fn restore_ops(&self, to_before: bool) -> Vec<StorageOp> {
+        self.changes
+            .iter()
+            .map(|change| {
+                let target = if to_before { &change.before } else { &change.after };
+                match target {
+                    Some(task) => StorageOp::Upsert(Box::new(task.clone())),
+                    None => StorageOp::Delete(change.id),
+                }
+            })
+            .collect()
+    }
+
+    // The archive's side of `restore_ops`: tasks moved out of the list go
+    // back into the archive, and those moved into it are dropped from it.
+    pub fn archive_ops(&self, to_before: bool) -> Vec<StorageOp> {
+        self.changes
+            .iter()
+            .filter(|change| change.archived)
+            .map(|change| {
+                let (target, other) = if to_before {
+                    (&change.before, &change.after)
+                } else {
+                    (&change.after, &change.before)
+                };
+                match (target, other) {
+                    (None, Some(task)) => StorageOp::Upsert(Box::new(task.clone())),
+                    _ => StorageOp::Delete(change.id),
+                }
+            })
+            .collect()
+    }
+}
+
+impl History {
+    pub fn new(path: PathBuf, depth: usize) -> Self {
+        History { path, depth }
+    }
+
+    pub fn load(&self) -> Result<Journal, io::Error> {
+        match fs::read_to_string(&self.path) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Journal::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, journal: &Journal) -> Result<(), io::Error> {
+        write_atomically(&self.path, serde_json::to_string(journal)?.as_bytes())
+    }
+
+    // A new change starts a new branch of history, so redo is cleared.
+    pub fn record(&self, entry: Entry) -> Result<(), io::Error> {
+        if self.depth == 0 {
+            return Ok(());
+        }
+        // The journal is only a convenience; never let a damaged one block changes.
+        let mut journal = match self.load() {
+            Err(e) if e.kind() == ErrorKind::InvalidData => Journal::default(),
+            journal => journal?,
+        };
+        journal.undo.push(entry);
+        let excess = journal.undo.len().saturating_sub(self.depth);
+        journal.undo.drain(..excess);
+        journal.redo.clear();
+        self.save(&journal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: usize) -> Task {
+        serde_json::from_value(serde_json::json!({ "id": id, "description": "Task", "status": "done" })).unwrap()
+    }
+
+    fn archive_move(id: usize, into_archive: bool) -> Change {
+        let (before, after) = if into_archive { (Some(task(id)), None) } else { (None, Some(task(id))) };
+        Change {
+            id,
+            before,
+            after,
+            archived: true,
+        }
+    }
+
+    fn ids(ops: &[StorageOp]) -> Vec<(bool, usize)> {
+        ops.iter().map(|op| (matches!(op, StorageOp::Upsert(_)), op.id())).collect()
+    }
+
+    #[test]
+    fn archive_moves_are_reversed_across_both_stores() {
+        let entry = Entry::new(vec![archive_move(1, true), archive_move(2, false)]);
+        assert_eq!(entry.summary(), "archive task 1, unarchive task 2");
+        // Undoing puts 1 back in the list and 2 back in the archive.
+        assert_eq!(ids(&entry.restore_ops(true)), [(true, 1), (false, 2)]);
+        assert_eq!(ids(&entry.archive_ops(true)), [(false, 1), (true, 2)]);
+        // Redoing moves them again.
+        assert_eq!(ids(&entry.restore_ops(false)), [(false, 1), (true, 2)]);
+        assert_eq!(ids(&entry.archive_ops(false)), [(true, 1), (false, 2)]);
+    }
+
+    #[test]
+    fn other_changes_leave_the_archive_alone() {
+        let purge = Change {
+            id: 1,
+            before: Some(task(1)),
+            after: None,
+            archived: false,
+        };
+        let entry = Entry::new(vec![purge]);
+        assert_eq!(entry.summary(), "purge task 1");
+        assert!(entry.archive_ops(true).is_empty());
+        // Older journals have no `archived` field.
+        let change: Change = serde_json::from_str(r#"{"id":1,"before":null,"after":null}"#).unwrap();
+        assert!(!change.archived);
+    }
+}
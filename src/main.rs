@@ -1,32 +1,260 @@
-use std::collections::HashMap;
+mod age;
+mod cli;
+mod color;
+mod config;
+mod deps;
+mod due;
+mod editor;
+mod filter;
+mod format;
+mod history;
+mod migrations;
+mod priority;
+mod recur;
+mod report;
+mod search;
+mod sqlite;
+mod status;
+mod table;
+mod tags;
+mod tree;
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::io::{self, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use chrono::{DateTime, Local, Utc};
+use clap::Parser;
 use serde::{Deserialize, Serialize};
 use std::io::Write;
 
+use cli::Cli;
+use color::Theme;
+use config::{Backend, Config};
+use due::{Due, DueView};
+use filter::Filter;
+use history::{Change, Entry, History};
+use priority::Priority;
+use recur::Recurrence;
+use sqlite::SqliteStorage;
+use status::Status;
+use table::{Layout, Row};
+use tree::ChildPolicy;
+
 trait TaskManager {
     fn add_task(&mut self, description: String) -> Result<usize, io::Error>;
-    fn complete_task(&mut self, id: usize) -> Result<(), io::Error>;
+    fn complete_task(&mut self, id: usize) -> Result<Option<usize>, io::Error>;
+    fn start_task(&mut self, id: usize) -> Result<(), io::Error>;
+    fn block_task(&mut self, id: usize) -> Result<(), io::Error>;
+    fn reopen_task(&mut self, id: usize) -> Result<(), io::Error>;
+    fn cancel_task(&mut self, id: usize) -> Result<(), io::Error>;
+    fn get_task(&self, id: usize) -> Option<&Task>;
     fn list_tasks(&self) -> Vec<&Task>;
+    fn list_actionable(&self) -> Vec<&Task>;
+    fn list_trash(&self) -> Vec<&Task>;
+    fn restore_task(&mut self, id: usize) -> Result<(), io::Error>;
+    fn purge_task(&mut self, id: usize) -> Result<(), io::Error>;
+    fn purge_trash(&mut self, deleted_before: Option<DateTime<Utc>>) -> Result<usize, io::Error>;
+    fn update_task(&mut self, id: usize, update: TaskUpdate) -> Result<(), io::Error>;
     fn delete_task(&mut self, id: usize) -> Result<(), io::Error>;
+    fn archive_tasks(&mut self, ids: &[usize]) -> Result<(), io::Error>;
+    fn archive_completed(&mut self, closed_before: Option<DateTime<Utc>>) -> Result<Vec<usize>, io::Error>;
+    fn unarchive_task(&mut self, id: usize) -> Result<(), io::Error>;
+    fn list_archive(&self) -> Result<Vec<Task>, io::Error>;
 }
 
 trait Storage {
     fn save(&self, tasks: &HashMap<usize, Task>) -> Result<(), io::Error>;
     fn load(&self) -> Result<HashMap<usize, Task>, io::Error>;
+
+    fn lock(&self) -> Result<StorageLock, io::Error> {
+        Ok(StorageLock { _file: None })
+    }
+
+    // Upgrades stored data to the current format and describes each step
+    // taken, or that would be taken with `dry_run`.
+    fn migrate(&self, _dry_run: bool) -> Result<Vec<String>, io::Error> {
+        Ok(Vec::new())
+    }
+
+    // Applies a batch of changes as one unit. The default falls back to
+    // loading and rewriting the whole map; backends that can update single
+    // tasks should override it.
+    fn apply(&self, changes: &[StorageOp]) -> Result<(), io::Error> {
+        let mut tasks = self.load()?;
+        for change in changes {
+            match change {
+                StorageOp::Upsert(task) => {
+                    tasks.insert(task.id.0, Task::clone(task));
+                }
+                StorageOp::Delete(id) => {
+                    tasks.remove(id);
+                }
+            }
+        }
+        self.save(&tasks)
+    }
+
+    fn upsert(&self, task: &Task) -> Result<(), io::Error> {
+        self.apply(&[StorageOp::Upsert(Box::new(task.clone()))])
+    }
+
+    fn delete(&self, id: usize) -> Result<(), io::Error> {
+        self.apply(&[StorageOp::Delete(id)])
+    }
+
+    fn max_id(&self) -> Result<Option<usize>, io::Error> {
+        Ok(self.load()?.keys().max().copied())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum StorageOp {
+    Upsert(Box<Task>),
+    Delete(usize),
+}
+
+impl StorageOp {
+    fn id(&self) -> usize {
+        match self {
+            StorageOp::Upsert(task) => task.id.0,
+            StorageOp::Delete(id) => *id,
+        }
+    }
+
+    // The operations that turn `before` into `after`, ordered by task ID.
+    fn diff(before: &HashMap<usize, Task>, after: &HashMap<usize, Task>) -> Vec<StorageOp> {
+        let mut ops: Vec<StorageOp> = after
+            .iter()
+            .filter(|(id, task)| before.get(id) != Some(task))
+            .map(|(_, task)| StorageOp::Upsert(Box::new(task.clone())))
+            .chain(
+                before
+                    .keys()
+                    .filter(|id| !after.contains_key(id))
+                    .map(|&id| StorageOp::Delete(id)),
+            )
+            .collect();
+        ops.sort_by_key(StorageOp::id);
+        ops
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+// Held for the duration of a load-modify-save cycle; the advisory lock is
+// released when the file handle is dropped.
+struct StorageLock {
+    _file: Option<fs::File>,
+}
+
+fn lock_file(path: &Path) -> Result<StorageLock, io::Error> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?;
+    file.lock()?;
+    Ok(StorageLock { _file: Some(file) })
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 struct TaskId(usize);
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 struct TaskDescription(String);
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 struct Task {
     id: TaskId,
     description: TaskDescription,
-    completed: bool,
+    status: Status,
+    // The task this is a subtask of.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent: Option<usize>,
+    // Tasks that have to be closed before this one can be started or done.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    depends_on: BTreeSet<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    due: Option<Due>,
+    // Completing the task schedules the next occurrence by this rule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    recurrence: Option<Recurrence>,
+    // The ID of the first task of a recurring series, shared by every occurrence.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    series: Option<usize>,
+    #[serde(default, skip_serializing_if = "Priority::is_none")]
+    priority: Priority,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    tags: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    modified_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    completed_at: Option<DateTime<Utc>>,
+    // Set while the task sits in the trash.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    deleted_at: Option<DateTime<Utc>>,
+}
+
+impl Task {
+    fn is_trashed(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    // When a done or cancelled task was closed; cancelling does not set
+    // `completed_at`, so the last modification stands in for it.
+    fn closed_at(&self) -> Option<DateTime<Utc>> {
+        if self.status.is_open() {
+            None
+        } else {
+            self.completed_at.or(self.modified_at)
+        }
+    }
+}
+
+// Fields left as `None` are kept; `Some(None)` clears an optional field.
+#[derive(Debug, Default, Clone)]
+struct TaskUpdate {
+    description: Option<String>,
+    notes: Option<Option<String>>,
+    due: Option<Option<Due>>,
+    priority: Option<Priority>,
+    tags: Option<BTreeSet<String>>,
+    parent: Option<Option<usize>>,
+    depends_on: Option<BTreeSet<usize>>,
+    recurrence: Option<Option<Recurrence>>,
+}
+
+impl TaskUpdate {
+    fn is_empty(&self) -> bool {
+        self.description.is_none()
+            && self.notes.is_none()
+            && self.due.is_none()
+            && self.priority.is_none()
+            && self.tags.is_none()
+            && self.parent.is_none()
+            && self.depends_on.is_none()
+            && self.recurrence.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+enum SortKey {
+    Id,
+    #[default]
+    Priority,
+    Due,
+    Created,
+    Modified,
+    Completed,
 }
 
 impl TaskDescription {
@@ -43,25 +271,138 @@ impl TaskDescription {
 }
 
 struct FileStorage {
-    filename: String,
+    filename: PathBuf,
 }
 
 impl FileStorage {
-    fn new(filename: String) -> Self {
+    fn new(filename: PathBuf) -> Self {
         FileStorage { filename }
     }
+
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let mut name = self.filename.file_name().unwrap_or_default().to_os_string();
+        name.push(suffix);
+        self.filename.with_file_name(name)
+    }
+
+    fn backup_path(&self) -> PathBuf {
+        self.sibling(".bak")
+    }
+
+    fn read_document(path: &Path) -> Result<serde_json::Value, io::Error> {
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    // Older formats are upgraded in memory; the file itself is rewritten in
+    // the current format on the next save.
+    fn read(path: &Path) -> Result<HashMap<usize, Task>, io::Error> {
+        let mut document = Self::read_document(path)?;
+        migrations::migrate(&mut document)?;
+        Self::tasks_from(document)
+    }
+
+    fn tasks_from(mut document: serde_json::Value) -> Result<HashMap<usize, Task>, io::Error> {
+        serde_json::from_value(document["tasks"].take())
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    // The previous good version is kept as `<file>.bak`; a corrupt file is
+    // never rotated over a good backup.
+    fn write_with_backup(&self, contents: &[u8]) -> Result<(), io::Error> {
+        if Self::read(&self.filename).is_ok() {
+            fs::copy(&self.filename, self.backup_path())?;
+        }
+        write_atomically(&self.filename, contents)
+    }
+}
+
+// Writes to a temp file in the same directory and renames it over the
+// original, so readers only ever see the old or the new contents.
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), io::Error> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    fs::create_dir_all(dir)?;
+
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(".tmp-{}", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+    let result = (|| {
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result?;
+
+    // Persist the rename itself; not every platform can open a directory.
+    if let Ok(dir) = fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct FileEnvelope<'a> {
+    version: u64,
+    tasks: &'a HashMap<usize, Task>,
 }
 
 impl Storage for FileStorage {
     fn save(&self, tasks: &HashMap<usize, Task>) -> Result<(), io::Error> {
-        let json = serde_json::to_string(tasks)?;
-        fs::write(&self.filename, json)
+        let json = serde_json::to_string(&FileEnvelope {
+            version: migrations::CURRENT_VERSION,
+            tasks,
+        })?;
+        self.write_with_backup(json.as_bytes())
+    }
+
+    fn migrate(&self, dry_run: bool) -> Result<Vec<String>, io::Error> {
+        let mut document = match Self::read_document(&self.filename) {
+            Ok(document) => document,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let steps = migrations::migrate(&mut document)?;
+        // Deserialising also checks that the upgraded tasks are valid.
+        let tasks = Self::tasks_from(document)?;
+        if !dry_run && !steps.is_empty() {
+            self.save(&tasks)?;
+        }
+        Ok(steps)
+    }
+
+    fn lock(&self) -> Result<StorageLock, io::Error> {
+        lock_file(&self.sibling(".lock"))
     }
 
     fn load(&self) -> Result<HashMap<usize, Task>, io::Error> {
-        match fs::read_to_string(&self.filename) {
-            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+        match Self::read(&self.filename) {
+            Ok(tasks) => Ok(tasks),
             Err(e) if e.kind() == ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) if e.kind() == ErrorKind::InvalidData => {
+                let backup = self.backup_path();
+                match Self::read(&backup) {
+                    Ok(tasks) => {
+                        eprintln!(
+                            "Warning: {} is corrupt ({}); loaded backup {}",
+                            self.filename.display(),
+                            e,
+                            backup.display()
+                        );
+                        Ok(tasks)
+                    }
+                    Err(_) => Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("{} is corrupt: {}", self.filename.display(), e),
+                    )),
+                }
+            }
             Err(e) => Err(e),
         }
     }
@@ -71,66 +412,669 @@ struct TodoList {
     tasks: HashMap<usize, Task>,
     storage: Box<dyn Storage>,
     next_id: usize,
+    in_batch: bool,
+    // Tasks the change in progress moves between the list and the archive,
+    // so the history can tell those moves from adds and purges.
+    archive_moves: BTreeSet<usize>,
+    history: Option<History>,
+    archive: Option<Box<dyn Storage>>,
+    child_policy: ChildPolicy,
 }
 
 impl TodoList {
     fn new(storage: Box<dyn Storage>) -> Result<Self, io::Error> {
-        let tasks = storage.load()?;
-        let next_id = tasks.keys().max().map_or(1, |&id| id + 1);
-        Ok(TodoList {
-            tasks,
+        let mut list = TodoList {
+            tasks: HashMap::new(),
             storage,
-            next_id,
+            next_id: 1,
+            in_batch: false,
+            archive_moves: BTreeSet::new(),
+            history: None,
+            archive: None,
+            child_policy: ChildPolicy::default(),
+        };
+        list.reload()?;
+        Ok(list)
+    }
+
+    fn with_history(mut self, history: History) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    fn with_child_policy(mut self, child_policy: ChildPolicy) -> Self {
+        self.child_policy = child_policy;
+        self
+    }
+
+    // The list is already loaded, so only the archive's IDs are read here.
+    fn with_archive(mut self, archive: Box<dyn Storage>) -> Result<Self, io::Error> {
+        if let Some(archived) = archive.max_id()? {
+            self.next_id = self.next_id.max(archived + 1);
+        }
+        self.archive = Some(archive);
+        Ok(self)
+    }
+
+    // Archived tasks keep their IDs, so new IDs start above those too.
+    fn reload(&mut self) -> Result<(), io::Error> {
+        self.tasks = self.storage.load()?;
+        let archived = match &self.archive {
+            Some(archive) => archive.max_id()?,
+            None => None,
+        };
+        self.next_id = self.tasks.keys().max().copied().max(archived).map_or(1, |id| id + 1);
+        Ok(())
+    }
+
+    fn archive_storage(&self) -> Result<&dyn Storage, io::Error> {
+        self.archive
+            .as_deref()
+            .ok_or_else(|| Error::new(ErrorKind::Unsupported, "No archive is configured"))
+    }
+
+    // Runs a change under the storage lock against a fresh copy of the file,
+    // so concurrent sessions never drop each other's tasks. If the task being
+    // changed was modified elsewhere since we last read it, the change is
+    // refused rather than overwriting the other session's edit. Only the
+    // tasks that actually changed are written back.
+    fn modify<T>(
+        &mut self,
+        id: Option<usize>,
+        change: impl FnOnce(&mut Self) -> Result<T, io::Error>,
+    ) -> Result<T, io::Error> {
+        if self.in_batch {
+            return change(self);
+        }
+        let _lock = self.storage.lock()?;
+        let seen = id.and_then(|id| self.tasks.get(&id).cloned());
+        self.reload()?;
+        if let (Some(id), Some(seen)) = (id, seen) {
+            match self.tasks.get(&id) {
+                Some(current) if *current == seen => {}
+                Some(_) => {
+                    return Err(Error::other(format!(
+                        "Task {} was changed by another session; review it and try again",
+                        id
+                    )))
+                }
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::NotFound,
+                        format!("Task {} was deleted by another session", id),
+                    ))
+                }
+            }
+        }
+        let before = self.tasks.clone();
+        self.archive_moves.clear();
+        let result = change(self)?;
+        let archive_moves = std::mem::take(&mut self.archive_moves);
+        let changes = StorageOp::diff(&before, &self.tasks);
+        if !changes.is_empty() {
+            self.storage.apply(&changes)?;
+            if let Some(history) = &self.history {
+                let changes = changes
+                    .iter()
+                    .map(|op| Change {
+                        id: op.id(),
+                        before: before.get(&op.id()).cloned(),
+                        after: self.tasks.get(&op.id()).cloned(),
+                        archived: archive_moves.contains(&op.id()),
+                    })
+                    .collect();
+                history.record(Entry::new(changes))?;
+            }
+        }
+        Ok(result)
+    }
+
+    fn undo(&mut self) -> Result<Option<Entry>, io::Error> {
+        self.step_history(true)
+    }
+
+    fn redo(&mut self) -> Result<Option<Entry>, io::Error> {
+        self.step_history(false)
+    }
+
+    // Moves the newest entry between the undo and redo stacks and restores
+    // the tasks it touched, moving them across to or from the archive where
+    // the entry did. Tasks changed since then by anything else are left
+    // alone and the step is refused.
+    fn step_history(&mut self, undo: bool) -> Result<Option<Entry>, io::Error> {
+        let Some(history) = &self.history else {
+            return Err(Error::new(ErrorKind::Unsupported, "Undo history is disabled"));
+        };
+        let _lock = self.storage.lock()?;
+        let mut journal = history.load()?;
+        let (from, to) = if undo {
+            (&mut journal.undo, &mut journal.redo)
+        } else {
+            (&mut journal.redo, &mut journal.undo)
+        };
+        let Some(entry) = from.pop() else {
+            return Ok(None);
+        };
+
+        self.tasks = self.storage.load()?;
+        let archive = match &self.archive {
+            Some(archive) => Some((archive.lock()?, archive.load()?)),
+            None => None,
+        };
+        let archived = archive.as_ref().map(|(_, archived)| archived);
+        for change in &entry.changes {
+            let (expected, other) = if undo {
+                (&change.after, &change.before)
+            } else {
+                (&change.before, &change.after)
+            };
+            let in_archive = archived.and_then(|archived| archived.get(&change.id));
+            let reason = if self.tasks.get(&change.id) != expected.as_ref() {
+                if self.tasks.contains_key(&change.id) || in_archive.is_none() {
+                    "has changed since"
+                } else {
+                    "has been archived since"
+                }
+            } else if change.archived && expected.is_none() && in_archive != other.as_ref() {
+                "has changed in the archive since"
+            } else {
+                continue;
+            };
+            return Err(Error::other(format!(
+                "Task {} {}; cannot {} '{}'",
+                change.id,
+                reason,
+                if undo { "undo" } else { "redo" },
+                entry.summary()
+            )));
+        }
+        // As with archiving, each task is written where it is going before
+        // it is removed from where it was.
+        let (archive_upserts, archive_deletes): (Vec<StorageOp>, Vec<StorageOp>) = entry
+            .archive_ops(undo)
+            .into_iter()
+            .partition(|op| matches!(op, StorageOp::Upsert(_)));
+        if !archive_upserts.is_empty() {
+            self.archive_storage()?.apply(&archive_upserts)?;
+        }
+        self.storage.apply(&entry.restore_ops(undo))?;
+        if !archive_deletes.is_empty() {
+            self.archive_storage()?.apply(&archive_deletes)?;
+        }
+        drop(archive);
+        to.push(entry.clone());
+        history.save(&journal)?;
+        self.reload()?;
+        Ok(Some(entry))
+    }
+
+    // Runs several operations as one change: a single lock, reload and write.
+    // If any of them fails nothing is saved.
+    fn batch<T>(
+        &mut self,
+        changes: impl FnOnce(&mut Self) -> Result<T, io::Error>,
+    ) -> Result<T, io::Error> {
+        let result = self.modify(None, |list| {
+            list.in_batch = true;
+            let result = changes(list);
+            list.in_batch = false;
+            result
+        });
+        if result.is_err() {
+            // Drop any half-applied changes.
+            self.reload()?;
+        }
+        result
+    }
+
+    fn migrate_storage(&mut self, dry_run: bool) -> Result<Vec<String>, io::Error> {
+        let _lock = self.storage.lock()?;
+        let mut steps = self.storage.migrate(dry_run)?;
+        if let Some(archive) = &self.archive {
+            let _archive_lock = archive.lock()?;
+            let archive_steps = archive.migrate(dry_run)?;
+            steps.extend(archive_steps.into_iter().map(|step| format!("archive: {}", step)));
+        }
+        self.reload()?;
+        Ok(steps)
+    }
+
+    // Moves the tasks picked by `select` from the list to the archive. The
+    // archive is written first, so a failure part-way leaves a task in both
+    // stores rather than in neither.
+    fn move_to_archive(
+        &mut self,
+        select: impl FnOnce(&Self) -> Result<Vec<usize>, io::Error>,
+    ) -> Result<Vec<usize>, io::Error> {
+        self.modify(None, |list| {
+            let ids = select(list)?;
+            if ids.is_empty() {
+                return Ok(ids);
+            }
+            let archive = list.archive_storage()?;
+            let _lock = archive.lock()?;
+            let ops: Vec<StorageOp> = ids
+                .iter()
+                .filter_map(|id| list.tasks.get(id))
+                .map(|task| StorageOp::Upsert(Box::new(task.clone())))
+                .collect();
+            archive.apply(&ops)?;
+            for id in &ids {
+                list.tasks.remove(id);
+            }
+            list.archive_moves.extend(&ids);
+            Ok(ids)
         })
     }
 
-    fn save(&self) -> Result<(), io::Error> {
-        self.storage.save(&self.tasks)
+    fn archive_expired(&mut self, archive_after_days: u32) -> Result<usize, io::Error> {
+        if archive_after_days == 0 {
+            return Ok(0);
+        }
+        let cutoff = Utc::now() - chrono::TimeDelta::days(archive_after_days.into());
+        let expired = |task: &Task| !task.is_trashed() && task.closed_at().is_some_and(|at| at < cutoff);
+        if !self.tasks.values().any(expired) {
+            return Ok(0);
+        }
+        Ok(self.archive_completed(Some(cutoff))?.len())
+    }
+
+    // Trashed tasks are hidden from every operation except restore and purge.
+    fn live_task_mut(&mut self, id: usize) -> Option<&mut Task> {
+        self.tasks.get_mut(&id).filter(|task| !task.is_trashed())
     }
-}
 
-impl TaskManager for TodoList {
-    fn add_task(&mut self, description: String) -> Result<usize, io::Error> {
-        let description = TaskDescription::new(description)?;
-        let id = TaskId(self.next_id);
-        
-        let task = Task {
-            id: id.clone(),
-            description,
-            completed: false,
+    fn purge_expired_trash(&mut self, retention_days: u32) -> Result<usize, io::Error> {
+        if retention_days == 0 {
+            return Ok(0);
+        }
+        let cutoff = Utc::now() - chrono::TimeDelta::days(retention_days.into());
+        if !self.tasks.values().any(|task| task.deleted_at.is_some_and(|at| at < cutoff)) {
+            return Ok(0);
+        }
+        // Housekeeping is not something the user did, so keep it out of undo.
+        let history = self.history.take();
+        let purged = self.purge_trash(Some(cutoff));
+        self.history = history;
+        purged
+    }
+
+    fn set_status(&mut self, id: usize, status: Status) -> Result<(), io::Error> {
+        self.modify(Some(id), |list| list.change_status(id, status))?;
+        Ok(())
+    }
+
+    // Returns the ID of the next occurrence when this completes a recurring task.
+    fn change_status(&mut self, id: usize, status: Status) -> Result<Option<usize>, io::Error> {
+        let task = self
+            .live_task_mut(id)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Task not found"))?;
+        let status = task.status.transition(status)?;
+        if matches!(status, Status::InProgress | Status::Done) {
+            let waiting = deps::waiting_on(&self.tasks, &self.tasks[&id]);
+            if !waiting.is_empty() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Task {} is waiting on task(s) {}", id, deps::format_ids(&waiting)),
+                ));
+            }
+        }
+        if !status.is_open() {
+            self.settle_children(id, Some(status))?;
+        }
+        if let Some(task) = self.live_task_mut(id) {
+            let now = Utc::now();
+            task.status = status;
+            task.modified_at = Some(now);
+            task.completed_at = (status == Status::Done).then_some(now);
+        }
+        if status == Status::Done {
+            self.schedule_next(id)
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Adds the next occurrence of a completed recurring task as a new task in
+    // the same series. The rule moves on to the new task, so reopening and
+    // completing an old occurrence does not schedule another one.
+    fn schedule_next(&mut self, id: usize) -> Result<Option<usize>, io::Error> {
+        let next_id = self.next_id;
+        let Some(task) = self.tasks.get_mut(&id) else {
+            return Ok(None);
         };
-        
-        self.tasks.insert(self.next_id, task);
+        let Some(recurrence) = task.recurrence.take() else {
+            return Ok(None);
+        };
+        let series = *task.series.get_or_insert(id);
+        let now = Utc::now();
+        let date = recurrence
+            .next_date(task.due.map(|due| due.local_date()), now.with_timezone(&Local).date_naive())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "The next occurrence is out of range"))?;
+        let next = Task {
+            id: TaskId(next_id),
+            status: Status::Todo,
+            due: Some(task.due.map_or(Due::Date(date), |due| due.with_date(date))),
+            recurrence: Some(recurrence),
+            series: Some(series),
+            created_at: Some(now),
+            modified_at: Some(now),
+            completed_at: None,
+            deleted_at: None,
+            ..task.clone()
+        };
+        self.tasks.insert(next_id, next);
         self.next_id += 1;
-        self.save()?;
-        
-        Ok(id.0)
+        Ok(Some(next_id))
     }
 
-    fn complete_task(&mut self, id: usize) -> Result<(), io::Error> {
-        match self.tasks.get_mut(&id) {
-            Some(task) => {
-                task.completed = true;
-                self.save()?;
+    fn trash_task(&mut self, id: usize) -> Result<(), io::Error> {
+        if self.live_task_mut(id).is_none() {
+            return Err(Error::new(ErrorKind::NotFound, "Task not found"));
+        }
+        self.settle_children(id, None)?;
+        let now = Utc::now();
+        if let Some(task) = self.live_task_mut(id) {
+            task.deleted_at = Some(now);
+            task.modified_at = Some(now);
+        }
+        // Links to the task stay so restoring it restores them; while it is in
+        // the trash it holds nothing up.
+        Ok(())
+    }
+
+    // Drops links to tasks that were purged, since nothing can wait on them.
+    fn forget_prerequisites(&mut self, purged: &[usize]) {
+        let now = Utc::now();
+        for task in self.tasks.values_mut() {
+            let before = task.depends_on.len();
+            task.depends_on.retain(|id| !purged.contains(id));
+            if task.depends_on.len() != before {
+                task.modified_at = Some(now);
+            }
+        }
+    }
+
+    // Applies the child policy to the subtasks of a task that is being closed
+    // with `status`, or deleted when `status` is `None`. Closing only concerns
+    // open subtasks; deleting concerns all of them.
+    fn settle_children(&mut self, id: usize, status: Option<Status>) -> Result<(), io::Error> {
+        let children: Vec<usize> = tree::children(self.tasks.values(), id)
+            .into_iter()
+            .filter(|child| status.is_none() || child.status.is_open())
+            .map(|child| child.id.0)
+            .collect();
+        if children.is_empty() {
+            return Ok(());
+        }
+        match self.child_policy {
+            ChildPolicy::Block => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Task {} has {} {}subtask(s); deal with them first or choose another child policy",
+                    id,
+                    children.len(),
+                    if status.is_some() { "open " } else { "" }
+                ),
+            )),
+            ChildPolicy::Cascade => children.into_iter().try_for_each(|child| match status {
+                Some(status) => self.change_status(child, status).map(|_| ()),
+                None => self.trash_task(child),
+            }),
+            ChildPolicy::Reparent => {
+                let grandparent = self.tasks.get(&id).and_then(|task| task.parent);
+                let now = Utc::now();
+                for child in children {
+                    if let Some(task) = self.tasks.get_mut(&child) {
+                        task.parent = grandparent;
+                        task.modified_at = Some(now);
+                    }
+                }
                 Ok(())
             }
-            None => Err(Error::new(ErrorKind::NotFound, "Task not found")),
         }
     }
+}
+
+impl TaskManager for TodoList {
+    fn add_task(&mut self, description: String) -> Result<usize, io::Error> {
+        let (description, tags) = tags::extract_tags(&description);
+        let description = TaskDescription::new(description)?;
+
+        self.modify(None, |list| {
+            let id = TaskId(list.next_id);
+            let now = Utc::now();
+
+            let task = Task {
+                id: id.clone(),
+                description,
+                status: Status::Todo,
+                parent: None,
+                depends_on: BTreeSet::new(),
+                due: None,
+                recurrence: None,
+                series: None,
+                priority: Priority::None,
+                tags,
+                notes: None,
+                created_at: Some(now),
+                modified_at: Some(now),
+                completed_at: None,
+                deleted_at: None,
+            };
+
+            list.tasks.insert(list.next_id, task);
+            list.next_id += 1;
+            Ok(id.0)
+        })
+    }
+
+    fn complete_task(&mut self, id: usize) -> Result<Option<usize>, io::Error> {
+        self.modify(Some(id), |list| list.change_status(id, Status::Done))
+    }
+
+    fn start_task(&mut self, id: usize) -> Result<(), io::Error> {
+        self.set_status(id, Status::InProgress)
+    }
+
+    fn block_task(&mut self, id: usize) -> Result<(), io::Error> {
+        self.set_status(id, Status::Blocked)
+    }
+
+    fn reopen_task(&mut self, id: usize) -> Result<(), io::Error> {
+        self.set_status(id, Status::Todo)
+    }
+
+    fn cancel_task(&mut self, id: usize) -> Result<(), io::Error> {
+        self.set_status(id, Status::Cancelled)
+    }
+
+    fn get_task(&self, id: usize) -> Option<&Task> {
+        self.tasks.get(&id).filter(|task| !task.is_trashed())
+    }
 
     fn list_tasks(&self) -> Vec<&Task> {
-        let mut tasks: Vec<&Task> = self.tasks.values().collect();
+        let mut tasks: Vec<&Task> = self.tasks.values().filter(|task| !task.is_trashed()).collect();
         tasks.sort_by_key(|task| task.id.0);
         tasks
     }
 
+    // Open tasks that can be worked on now: not blocked, not waiting on
+    // another task and without open subtasks of their own.
+    fn list_actionable(&self) -> Vec<&Task> {
+        let tasks = self.list_tasks();
+        let with_open_children: HashSet<usize> = tasks
+            .iter()
+            .filter(|task| task.status.is_open())
+            .filter_map(|task| task.parent)
+            .collect();
+        tasks
+            .into_iter()
+            .filter(|task| task.status.is_open() && task.status != Status::Blocked)
+            .filter(|task| deps::waiting_on(&self.tasks, task).is_empty())
+            .filter(|task| !with_open_children.contains(&task.id.0))
+            .collect()
+    }
+
+    fn list_trash(&self) -> Vec<&Task> {
+        let mut tasks: Vec<&Task> = self.tasks.values().filter(|task| task.is_trashed()).collect();
+        tasks.sort_by_key(|task| (Reverse(task.deleted_at), task.id.0));
+        tasks
+    }
+
+    fn restore_task(&mut self, id: usize) -> Result<(), io::Error> {
+        self.modify(Some(id), |list| match list.tasks.get_mut(&id) {
+            Some(task) if task.is_trashed() => {
+                task.deleted_at = None;
+                task.modified_at = Some(Utc::now());
+                Ok(())
+            }
+            _ => Err(Error::new(ErrorKind::NotFound, "Task not found in trash")),
+        })
+    }
+
+    fn purge_task(&mut self, id: usize) -> Result<(), io::Error> {
+        self.modify(Some(id), |list| match list.tasks.get(&id) {
+            Some(task) if task.is_trashed() => {
+                list.tasks.remove(&id);
+                list.forget_prerequisites(&[id]);
+                Ok(())
+            }
+            _ => Err(Error::new(ErrorKind::NotFound, "Task not found in trash")),
+        })
+    }
+
+    fn purge_trash(&mut self, deleted_before: Option<DateTime<Utc>>) -> Result<usize, io::Error> {
+        self.modify(None, |list| {
+            let purged: Vec<usize> = list
+                .tasks
+                .values()
+                .filter(|task| match (task.deleted_at, deleted_before) {
+                    (Some(deleted_at), Some(cutoff)) => deleted_at < cutoff,
+                    (Some(_), None) => true,
+                    (None, _) => false,
+                })
+                .map(|task| task.id.0)
+                .collect();
+            for id in &purged {
+                list.tasks.remove(id);
+            }
+            list.forget_prerequisites(&purged);
+            Ok(purged.len())
+        })
+    }
+
+    fn update_task(&mut self, id: usize, update: TaskUpdate) -> Result<(), io::Error> {
+        let description = update.description.map(TaskDescription::new).transpose()?;
+        self.modify(Some(id), |list| {
+            if let Some(Some(parent)) = update.parent {
+                tree::check_parent(&list.tasks, id, parent)?;
+            }
+            if let Some(depends_on) = &update.depends_on {
+                deps::check(&list.tasks, id, depends_on)?;
+            }
+            match list.live_task_mut(id) {
+                Some(task) => {
+                    if let Some(description) = description {
+                        task.description = description;
+                    }
+                    if let Some(notes) = update.notes {
+                        task.notes = notes.filter(|notes| !notes.trim().is_empty());
+                    }
+                    if let Some(due) = update.due {
+                        task.due = due;
+                    }
+                    if let Some(priority) = update.priority {
+                        task.priority = priority;
+                    }
+                    if let Some(tags) = update.tags {
+                        task.tags = tags;
+                    }
+                    if let Some(parent) = update.parent {
+                        task.parent = parent;
+                    }
+                    if let Some(depends_on) = update.depends_on {
+                        task.depends_on = depends_on;
+                    }
+                    if let Some(recurrence) = update.recurrence {
+                        task.recurrence = recurrence;
+                    }
+                    task.modified_at = Some(Utc::now());
+                    Ok(())
+                }
+                None => Err(Error::new(ErrorKind::NotFound, "Task not found")),
+            }
+        })
+    }
+
+    // Moves the task to the trash; it keeps its ID until it is purged.
     fn delete_task(&mut self, id: usize) -> Result<(), io::Error> {
-        if self.tasks.remove(&id).is_some() {
-            self.save()?;
+        self.modify(Some(id), |list| list.trash_task(id))
+    }
+
+    fn archive_tasks(&mut self, ids: &[usize]) -> Result<(), io::Error> {
+        self.move_to_archive(|list| {
+            for &id in ids {
+                match list.get_task(id) {
+                    Some(task) if task.status.is_open() => {
+                        return Err(Error::new(
+                            ErrorKind::InvalidInput,
+                            format!("Task {} is still open; complete or cancel it first", id),
+                        ))
+                    }
+                    Some(_) => {}
+                    None => {
+                        return Err(Error::new(
+                            ErrorKind::NotFound,
+                            format!("Task {} not found", id),
+                        ))
+                    }
+                }
+            }
+            Ok(ids.to_vec())
+        })?;
+        Ok(())
+    }
+
+    // Archives every done or cancelled task, or only those closed before the cutoff.
+    fn archive_completed(&mut self, closed_before: Option<DateTime<Utc>>) -> Result<Vec<usize>, io::Error> {
+        self.move_to_archive(|list| {
+            Ok(list
+                .list_tasks()
+                .into_iter()
+                .filter(|task| !task.status.is_open())
+                .filter(|task| {
+                    closed_before.is_none_or(|cutoff| task.closed_at().is_some_and(|at| at < cutoff))
+                })
+                .map(|task| task.id.0)
+                .collect())
+        })
+    }
+
+    // The task goes back into the list first and is only then dropped from
+    // the archive, so it is never lost in between.
+    fn unarchive_task(&mut self, id: usize) -> Result<(), io::Error> {
+        self.modify(None, |list| {
+            let archive = list.archive_storage()?;
+            let task = {
+                let _lock = archive.lock()?;
+                archive.load()?.remove(&id)
+            };
+            let task = task.ok_or_else(|| Error::new(ErrorKind::NotFound, "Task not found in archive"))?;
+            // A copy left behind by an interrupted move is older than the list's.
+            list.tasks.entry(id).or_insert(task);
+            list.archive_moves.insert(id);
             Ok(())
-        } else {
-            Err(Error::new(ErrorKind::NotFound, "Task not found"))
-        }
+        })?;
+        let archive = self.archive_storage()?;
+        let _lock = archive.lock()?;
+        archive.delete(id)
+    }
+
+    fn list_archive(&self) -> Result<Vec<Task>, io::Error> {
+        let mut tasks: Vec<Task> = self.archive_storage()?.load()?.into_values().collect();
+        tasks.sort_by_key(|task| task.id.0);
+        Ok(tasks)
     }
 }
 
@@ -140,8 +1084,28 @@ fn print_menu() {
     println!("2. List tasks");
     println!("3. Complete task");
     println!("4. Delete task");
-    println!("5. Exit");
-    print!("\nChoose an option (1-5): ");
+    println!("5. Show upcoming tasks");
+    println!("6. Set due date");
+    println!("7. Set priority");
+    println!("8. Edit tags");
+    println!("9. List tags");
+    println!("10. Edit task");
+    println!("11. Change task status");
+    println!("12. Undo");
+    println!("13. Redo");
+    println!("14. View trash");
+    println!("15. Restore task from trash");
+    println!("16. Empty trash");
+    println!("17. Archive completed tasks");
+    println!("18. View or search archive");
+    println!("19. Restore task from archive");
+    println!("20. Set parent task");
+    println!("21. Set dependencies");
+    println!("22. Show next actionable tasks");
+    println!("23. Set recurrence");
+    println!("24. Search tasks");
+    println!("25. Exit");
+    print!("\nChoose an option (1-25): ");
     io::stdout().flush().unwrap();
 }
 
@@ -153,11 +1117,294 @@ fn get_input(prompt: &str) -> String {
     input.trim().to_string()
 }
 
-fn main() -> Result<(), io::Error> {
-    let storage = Box::new(FileStorage::new("todo.json".to_string()));
-    let mut todo_list = TodoList::new(storage)?;
+fn print_task(task: &Task) {
+    print_task_at(task, 0, None, &[], None);
+}
+
+// Prints a task indented `depth` levels, with the done/total count of its
+// subtasks if it has any and the open tasks it is waiting on.
+fn print_task_at(
+    task: &Task,
+    depth: usize,
+    progress: Option<(usize, usize)>,
+    waiting: &[usize],
+    theme: Option<&Theme>,
+) {
+    let due = match &task.due {
+        Some(due) if task.status.is_open() && due.is_overdue(Local::now()) => format!(" (overdue: {})", due),
+        Some(due) => format!(" (due {})", due),
+        None => String::new(),
+    };
+    let recurrence = match &task.recurrence {
+        Some(recurrence) => format!(" (repeats {})", recurrence),
+        None => String::new(),
+    };
+    let priority = if task.priority.is_none() {
+        String::new()
+    } else {
+        format!("{} ", task.priority.marker())
+    };
+    let tags: String = task.tags.iter().map(|tag| format!(" +{}", tag)).collect();
+    let progress = match progress {
+        Some((done, total)) => format!(" ({}/{} done)", done, total),
+        None => String::new(),
+    };
+    // Tasks waiting on others are shown as blocked, whatever their own status.
+    let (marker, waiting) = if waiting.is_empty() || !task.status.is_open() {
+        (task.status.marker(), String::new())
+    } else {
+        (Status::Blocked.marker(), format!(" (waiting on {})", deps::format_ids(waiting)))
+    };
+    let age = match task.created_at {
+        Some(created_at) => format!(" (age {})", age::format_age(created_at, Utc::now())),
+        None => String::new(),
+    };
+    let line = format!(
+        "{}. [{}] {}{}{}{}{}{}{}{}",
+        task.id.0,
+        marker,
+        priority,
+        task.description.get(),
+        progress,
+        tags,
+        due,
+        recurrence,
+        waiting,
+        age
+    );
+    let line = match theme {
+        Some(theme) => theme.task(task, Local::now()).paint(&line),
+        None => line,
+    };
+    println!("{}{}", "  ".repeat(depth), line);
+}
+
+// Prints `tasks` with subtask counts and open prerequisites taken from all
+// of `todo_list`'s tasks, as a table on a terminal and as plain lines
+// otherwise. As a tree, each task is listed under its parent when the parent
+// is listed too.
+fn print_tasks(tasks: &[&Task], todo_list: &TodoList, as_tree: bool, layout: &Layout) {
+    let all = todo_list.list_tasks();
+    let arranged = if as_tree {
+        tree::arrange(tasks)
+    } else {
+        tasks.iter().map(|&task| (0, task)).collect()
+    };
+    let rows: Vec<Row> = arranged
+        .into_iter()
+        .map(|(depth, task)| Row {
+            task,
+            depth,
+            progress: tree::progress(all.iter().copied(), task.id.0),
+            waiting: deps::waiting_on(&todo_list.tasks, task),
+        })
+        .collect();
+    match layout.width {
+        Some(width) => {
+            for line in table::render(&rows, layout, width) {
+                println!("{}", line);
+            }
+        }
+        None => {
+            for row in rows {
+                print_task_at(row.task, row.depth, row.progress, &row.waiting, layout.theme.as_ref());
+            }
+        }
+    }
+}
+
+fn print_search_hit(hit: &search::Hit, theme: Option<&Theme>) {
+    let highlight = theme.map(|theme| &theme.highlight);
+    let task = hit.task;
+    let tags: String = task.tags.iter().map(|tag| format!(" +{}", tag)).collect();
+    println!(
+        "{}. [{}] {}{}",
+        task.id.0,
+        task.status.marker(),
+        search::highlight(task.description.get(), &hit.description, highlight),
+        tags
+    );
+    if let Some(notes) = &task.notes {
+        for line in search::matching_lines(notes, &hit.notes, highlight) {
+            println!("    {}", line.trim_start());
+        }
+    }
+}
+
+fn print_trashed_task(task: &Task) {
+    let deleted = match task.deleted_at {
+        Some(deleted_at) => format!(" (deleted {})", age::format_ago(deleted_at, Utc::now())),
+        None => String::new(),
+    };
+    println!("{}. {}{}", task.id.0, task.description.get(), deleted);
+}
 
+fn print_tag_counts(tasks: Vec<&Task>) {
+    let counts = tags::tag_counts(tasks);
+    if counts.is_empty() {
+        println!("No tags found.");
+        return;
+    }
+    let width = counts.keys().map(|tag| tag.chars().count()).max().unwrap_or(0) + 1;
+    for (tag, count) in counts {
+        println!(
+            "{:<width$}  {} open, {} completed",
+            format!("+{}", tag),
+            count.open,
+            count.completed,
+            width = width
+        );
+    }
+}
+
+// Case-insensitive match of `query` against the description, notes and tags.
+fn task_contains(task: &Task, query: &str) -> bool {
+    let query = query.trim().to_lowercase();
+    let contains = |text: &str| text.to_lowercase().contains(&query);
+    contains(task.description.get())
+        || task.notes.as_deref().is_some_and(contains)
+        || task.tags.iter().any(|tag| contains(tag))
+}
+
+// Sorts by each key in turn, later keys breaking ties left by earlier ones,
+// and by ID last.
+fn sort_tasks(tasks: &mut [&Task], keys: &[SortKey]) {
+    tasks.sort_by(|a, b| {
+        keys.iter()
+            .fold(Ordering::Equal, |ordering, key| ordering.then_with(|| key.compare(a, b)))
+            .then(a.id.0.cmp(&b.id.0))
+    });
+}
+
+impl SortKey {
+    fn compare(&self, a: &Task, b: &Task) -> Ordering {
+        let by_due = |task: &Task| (task.due.is_none(), task.due);
+        match self {
+            SortKey::Id => a.id.0.cmp(&b.id.0),
+            SortKey::Priority => (Reverse(a.priority), by_due(a)).cmp(&(Reverse(b.priority), by_due(b))),
+            SortKey::Due => (by_due(a), Reverse(a.priority)).cmp(&(by_due(b), Reverse(b.priority))),
+            // Oldest first; tasks from before timestamps were recorded sort last.
+            SortKey::Created => (a.created_at.is_none(), a.created_at).cmp(&(b.created_at.is_none(), b.created_at)),
+            // Most recent first for modification and completion times.
+            SortKey::Modified => b.modified_at.cmp(&a.modified_at),
+            SortKey::Completed => b.completed_at.cmp(&a.completed_at),
+        }
+    }
+}
+
+fn parse_optional_due(input: &str) -> Result<Option<Due>, io::Error> {
+    if input.trim().is_empty() {
+        Ok(None)
+    } else {
+        Due::parse(input, Local::now()).map(Some)
+    }
+}
+
+fn parse_optional_id(input: &str) -> Result<Option<usize>, io::Error> {
+    if input.trim().is_empty() {
+        Ok(None)
+    } else {
+        input
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "Invalid ID format"))
+    }
+}
+
+fn tasks_due(tasks: Vec<&Task>, view: DueView, now: DateTime<Local>) -> Vec<&Task> {
+    let mut tasks: Vec<&Task> = tasks
+        .into_iter()
+        .filter(|task| task.status.is_open())
+        .filter(|task| task.due.is_some_and(|due| view.matches(&due, now)))
+        .collect();
+    tasks.sort_by_key(|task| (task.due, task.id.0));
+    tasks
+}
+
+// Adds a task and sets the fields in `update` on it as one change; `tags`
+// come on top of any +tag words in the description.
+fn add_task_with(
+    todo_list: &mut TodoList,
+    description: String,
+    tags: &[String],
+    mut update: TaskUpdate,
+) -> Result<usize, io::Error> {
+    let tags = tags
+        .iter()
+        .map(|tag| tags::parse_tag(tag))
+        .collect::<Result<BTreeSet<_>, _>>()?;
+    todo_list.batch(|list| {
+        let id = list.add_task(description)?;
+        if !tags.is_empty() {
+            let mut all_tags = list.get_task(id).map(|task| task.tags.clone()).unwrap_or_default();
+            all_tags.extend(tags);
+            update.tags = Some(all_tags);
+        }
+        if !update.is_empty() {
+            list.update_task(id, update)?;
+        }
+        Ok(id)
+    })
+}
+
+fn print_next_occurrence(todo_list: &TodoList, next: Option<usize>) {
+    if let Some(task) = next.and_then(|id| todo_list.get_task(id)) {
+        match task.due {
+            Some(due) => println!("Next occurrence is task {}, due {}", task.id.0, due),
+            None => println!("Next occurrence is task {}", task.id.0),
+        }
+    }
+}
+
+// Returns the update produced by `$EDITOR`, or `None` if the edit was aborted.
+fn update_from_editor(task: &Task) -> Result<Option<TaskUpdate>, io::Error> {
+    Ok(editor::edit_task_text(task)?.map(|(description, notes)| TaskUpdate {
+        description: Some(description),
+        notes: Some(notes),
+        ..TaskUpdate::default()
+    }))
+}
+
+fn edit_task_interactively(todo_list: &mut TodoList, id: usize) -> Result<bool, io::Error> {
+    let task = todo_list
+        .get_task(id)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "Task not found"))?;
+    print_task(task);
+
+    let update = if get_input("Open in $EDITOR? (y/N): ").eq_ignore_ascii_case("y") {
+        update_from_editor(task)?
+    } else {
+        let description = get_input("New description (empty to keep): ");
+        let due = get_input("New due date (empty to keep, '-' to clear): ");
+        let priority = get_input("New priority (empty to keep): ");
+        Some(TaskUpdate {
+            description: (!description.is_empty()).then_some(description),
+            due: match due.as_str() {
+                "" => None,
+                "-" => Some(None),
+                due => Some(Some(Due::parse(due, Local::now())?)),
+            },
+            priority: (!priority.is_empty()).then(|| priority.parse()).transpose()?,
+            ..TaskUpdate::default()
+        })
+    };
+
+    match update {
+        Some(update) if !update.is_empty() => {
+            todo_list.update_task(id, update)?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+fn run_menu(todo_list: &mut TodoList, layout: &Layout) -> Result<(), io::Error> {
     loop {
+        // Pick up changes made by other sessions before showing anything.
+        if let Err(e) = todo_list.reload() {
+            println!("Error: {}", e);
+        }
         print_menu();
         
         let choice = get_input("");
@@ -165,25 +1412,46 @@ fn main() -> Result<(), io::Error> {
         match choice.as_str() {
             "1" => {
                 let description = get_input("Enter task description: ");
-                match todo_list.add_task(description) {
+                let due = get_input("Enter due date (optional): ");
+                let priority = get_input("Enter priority (none/low/medium/high/critical, optional): ");
+                let parent = get_input("Enter parent task ID (optional): ");
+                match parse_optional_due(&due).and_then(|due| {
+                    let update = TaskUpdate {
+                        due: due.map(Some),
+                        priority: Some(priority.parse::<Priority>()?),
+                        parent: parse_optional_id(&parent)?.map(Some),
+                        ..TaskUpdate::default()
+                    };
+                    add_task_with(todo_list, description, &[], update)
+                }) {
                     Ok(id) => println!("Added task with ID: {}", id),
                     Err(e) => println!("Error: {}", e),
                 }
             },
             "2" => {
-                let tasks = todo_list.list_tasks();
+                let query = get_input("Enter filter (e.g. status:open and tag:work, optional): ");
+                let filter = match query.trim() {
+                    "" => None,
+                    query => match Filter::parse(query) {
+                        Ok(filter) => Some(filter),
+                        Err(e) => {
+                            println!("Error: {}", e);
+                            continue;
+                        }
+                    },
+                };
+                let context = filter::Context::new(Local::now(), todo_list.list_actionable());
+                let mut tasks: Vec<&Task> = todo_list
+                    .list_tasks()
+                    .into_iter()
+                    .filter(|task| filter.as_ref().is_none_or(|filter| filter.matches(task, &context)))
+                    .collect();
+                sort_tasks(&mut tasks, &[SortKey::Priority]);
                 if tasks.is_empty() {
                     println!("No tasks found.");
                 } else {
-                    println!("\nAll tasks:");
-                    for task in tasks {
-                        println!(
-                            "{}. [{}] {}",
-                            task.id.0,
-                            if task.completed { "✓" } else { " " },
-                            task.description.get()
-                        );
-                    }
+                    println!("\n{}:", if filter.is_some() { "Matching tasks" } else { "All tasks" });
+                    print_tasks(&tasks, todo_list, true, layout);
                 }
             },
             "3" => {
@@ -191,7 +1459,10 @@ fn main() -> Result<(), io::Error> {
                 match id_str.parse::<usize>() {
                     Ok(id) => {
                         match todo_list.complete_task(id) {
-                            Ok(_) => println!("Marked task {} as complete", id),
+                            Ok(next) => {
+                                println!("Marked task {} as complete", id);
+                                print_next_occurrence(todo_list, next);
+                            }
                             Err(e) => println!("Error: {}", e),
                         }
                     },
@@ -211,6 +1482,262 @@ fn main() -> Result<(), io::Error> {
                 }
             },
             "5" => {
+                let now = Local::now();
+                // The week includes today, so each task is shown in the first view it fits.
+                let mut shown = HashSet::new();
+                for view in [DueView::Overdue, DueView::Today, DueView::Week] {
+                    let mut tasks = tasks_due(todo_list.list_tasks(), view, now);
+                    tasks.retain(|task| shown.insert(task.id.0));
+                    if !tasks.is_empty() {
+                        println!("\n{}:", view.title());
+                        for task in tasks {
+                            print_task(task);
+                        }
+                    }
+                }
+            },
+            "6" => {
+                let id_str = get_input("Enter task ID: ");
+                match id_str.parse::<usize>() {
+                    Ok(id) => {
+                        let due = get_input("Enter due date (empty to clear): ");
+                        match parse_optional_due(&due).and_then(|due| {
+                            todo_list.update_task(id, TaskUpdate { due: Some(due), ..TaskUpdate::default() })
+                        }) {
+                            Ok(_) => println!("Updated due date of task {}", id),
+                            Err(e) => println!("Error: {}", e),
+                        }
+                    },
+                    Err(_) => println!("Invalid ID format"),
+                }
+            },
+            "7" => {
+                let id_str = get_input("Enter task ID: ");
+                match id_str.parse::<usize>() {
+                    Ok(id) => {
+                        let priority = get_input("Enter priority (none/low/medium/high/critical): ");
+                        match priority
+                            .parse::<Priority>()
+                            .and_then(|priority| {
+                                todo_list.update_task(id, TaskUpdate { priority: Some(priority), ..TaskUpdate::default() })
+                            })
+                        {
+                            Ok(_) => println!("Updated priority of task {}", id),
+                            Err(e) => println!("Error: {}", e),
+                        }
+                    },
+                    Err(_) => println!("Invalid ID format"),
+                }
+            },
+            "8" => {
+                let id_str = get_input("Enter task ID: ");
+                match id_str.parse::<usize>() {
+                    Ok(id) => {
+                        let changes = get_input("Enter tags to add (+tag) or remove (-tag): ");
+                        let changes: Vec<String> = changes.split_whitespace().map(String::from).collect();
+                        let result = match todo_list.get_task(id) {
+                            Some(task) => tags::apply_changes(&task.tags, &changes),
+                            None => Err(Error::new(ErrorKind::NotFound, "Task not found")),
+                        };
+                        match result.and_then(|tags| {
+                            todo_list.update_task(id, TaskUpdate { tags: Some(tags), ..TaskUpdate::default() })
+                        }) {
+                            Ok(_) => println!("Updated tags of task {}", id),
+                            Err(e) => println!("Error: {}", e),
+                        }
+                    },
+                    Err(_) => println!("Invalid ID format"),
+                }
+            },
+            "9" => print_tag_counts(todo_list.list_tasks()),
+            "10" => {
+                let id_str = get_input("Enter task ID to edit: ");
+                match id_str.parse::<usize>() {
+                    Ok(id) => match edit_task_interactively(todo_list, id) {
+                        Ok(true) => println!("Updated task {}", id),
+                        Ok(false) => println!("Task {} left unchanged", id),
+                        Err(e) => println!("Error: {}", e),
+                    },
+                    Err(_) => println!("Invalid ID format"),
+                }
+            },
+            "11" => {
+                let id_str = get_input("Enter task ID: ");
+                match id_str.parse::<usize>() {
+                    Ok(id) => {
+                        let action = get_input("(s)tart, (b)lock, (r)eopen or (c)ancel: ");
+                        let result = match action.to_lowercase().as_str() {
+                            "s" | "start" => todo_list.start_task(id),
+                            "b" | "block" => todo_list.block_task(id),
+                            "r" | "reopen" => todo_list.reopen_task(id),
+                            "c" | "cancel" => todo_list.cancel_task(id),
+                            _ => Err(Error::new(ErrorKind::InvalidInput, "Unknown action")),
+                        };
+                        match result {
+                            Ok(_) => println!("Updated status of task {}", id),
+                            Err(e) => println!("Error: {}", e),
+                        }
+                    },
+                    Err(_) => println!("Invalid ID format"),
+                }
+            },
+            "12" => match todo_list.undo() {
+                Ok(Some(entry)) => println!("Undid: {}", entry.summary()),
+                Ok(None) => println!("Nothing to undo."),
+                Err(e) => println!("Error: {}", e),
+            },
+            "13" => match todo_list.redo() {
+                Ok(Some(entry)) => println!("Redid: {}", entry.summary()),
+                Ok(None) => println!("Nothing to redo."),
+                Err(e) => println!("Error: {}", e),
+            },
+            "14" => {
+                let tasks = todo_list.list_trash();
+                if tasks.is_empty() {
+                    println!("Trash is empty.");
+                } else {
+                    println!("\nTrash:");
+                    for task in tasks {
+                        print_trashed_task(task);
+                    }
+                }
+            },
+            "15" => {
+                let id_str = get_input("Enter task ID to restore: ");
+                match id_str.parse::<usize>() {
+                    Ok(id) => match todo_list.restore_task(id) {
+                        Ok(_) => println!("Restored task {}", id),
+                        Err(e) => println!("Error: {}", e),
+                    },
+                    Err(_) => println!("Invalid ID format"),
+                }
+            },
+            "16" => {
+                if get_input("Permanently delete everything in the trash? (y/N): ").eq_ignore_ascii_case("y") {
+                    match todo_list.purge_trash(None) {
+                        Ok(count) => println!("Purged {} task(s)", count),
+                        Err(e) => println!("Error: {}", e),
+                    }
+                }
+            },
+            "17" => match todo_list.archive_completed(None) {
+                Ok(ids) => println!("Archived {} task(s)", ids.len()),
+                Err(e) => println!("Error: {}", e),
+            },
+            "18" => {
+                let query = get_input("Search for (empty to list all): ");
+                match todo_list.list_archive() {
+                    Ok(tasks) => {
+                        let tasks: Vec<&Task> = tasks.iter().filter(|task| task_contains(task, &query)).collect();
+                        if tasks.is_empty() {
+                            println!("No archived tasks found.");
+                        } else {
+                            println!("\nArchive:");
+                            for task in tasks {
+                                print_task(task);
+                            }
+                        }
+                    }
+                    Err(e) => println!("Error: {}", e),
+                }
+            },
+            "19" => {
+                let id_str = get_input("Enter task ID to restore: ");
+                match id_str.parse::<usize>() {
+                    Ok(id) => match todo_list.unarchive_task(id) {
+                        Ok(_) => println!("Restored task {} from the archive", id),
+                        Err(e) => println!("Error: {}", e),
+                    },
+                    Err(_) => println!("Invalid ID format"),
+                }
+            },
+            "20" => {
+                let id_str = get_input("Enter task ID: ");
+                match id_str.parse::<usize>() {
+                    Ok(id) => {
+                        let parent = get_input("Enter parent task ID (empty for top level): ");
+                        match parse_optional_id(&parent).and_then(|parent| {
+                            todo_list.update_task(id, TaskUpdate { parent: Some(parent), ..TaskUpdate::default() })
+                        }) {
+                            Ok(_) => println!("Updated parent of task {}", id),
+                            Err(e) => println!("Error: {}", e),
+                        }
+                    },
+                    Err(_) => println!("Invalid ID format"),
+                }
+            },
+            "21" => {
+                let id_str = get_input("Enter task ID: ");
+                match id_str.parse::<usize>() {
+                    Ok(id) => {
+                        let ids = get_input("Enter IDs of the tasks it waits on (empty for none): ");
+                        let depends_on = ids
+                            .split(|c: char| c == ',' || c.is_whitespace())
+                            .filter(|id| !id.is_empty())
+                            .map(|id| id.parse::<usize>())
+                            .collect::<Result<BTreeSet<_>, _>>();
+                        match depends_on {
+                            Ok(depends_on) => match todo_list.update_task(
+                                id,
+                                TaskUpdate { depends_on: Some(depends_on), ..TaskUpdate::default() },
+                            ) {
+                                Ok(_) => println!("Updated dependencies of task {}", id),
+                                Err(e) => println!("Error: {}", e),
+                            },
+                            Err(_) => println!("Invalid ID format"),
+                        }
+                    },
+                    Err(_) => println!("Invalid ID format"),
+                }
+            },
+            "22" => {
+                let mut tasks = todo_list.list_actionable();
+                sort_tasks(&mut tasks, &[SortKey::Priority]);
+                if tasks.is_empty() {
+                    println!("Nothing to do right now.");
+                } else {
+                    println!("\nNext:");
+                    print_tasks(&tasks, todo_list, false, layout);
+                }
+            },
+            "23" => {
+                let id_str = get_input("Enter task ID: ");
+                match id_str.parse::<usize>() {
+                    Ok(id) => {
+                        let rule = get_input("Repeat (daily, weekly:mon,thu, monthly:15, every:3d, after:2w; empty to stop): ");
+                        let recurrence = if rule.is_empty() { Ok(None) } else { rule.parse().map(Some) };
+                        match recurrence.and_then(|recurrence| {
+                            todo_list.update_task(id, TaskUpdate { recurrence: Some(recurrence), ..TaskUpdate::default() })
+                        }) {
+                            Ok(_) => println!("Updated recurrence of task {}", id),
+                            Err(e) => println!("Error: {}", e),
+                        }
+                    },
+                    Err(_) => println!("Invalid ID format"),
+                }
+            },
+            "24" => {
+                let query = get_input("Search for: ");
+                let mode = get_input("Match as (s)ubstring, (w)ord, (r)egex or (f)uzzy [s]: ");
+                let mode = match mode.to_lowercase().as_str() {
+                    "" | "s" | "substring" => Ok(search::Mode::Substring),
+                    "w" | "word" => Ok(search::Mode::Word),
+                    "r" | "regex" => Ok(search::Mode::Regex),
+                    "f" | "fuzzy" => Ok(search::Mode::Fuzzy),
+                    _ => Err(Error::new(ErrorKind::InvalidInput, "Unknown match mode")),
+                };
+                match mode.and_then(|mode| search::Search::new(&query, mode)) {
+                    Ok(search) => {
+                        let hits = search.run(todo_list.list_tasks());
+                        if hits.is_empty() {
+                            println!("No matching tasks.");
+                        }
+                        hits.iter().for_each(|hit| print_search_hit(hit, layout.theme.as_ref()));
+                    }
+                    Err(e) => println!("Error: {}", e),
+                }
+            },
+            "25" => {
                 println!("Goodbye!");
                 break;
             },
@@ -219,4 +1746,56 @@ fn main() -> Result<(), io::Error> {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+fn exit_code(error: &io::Error) -> ExitCode {
+    match error.kind() {
+        ErrorKind::NotFound => ExitCode::from(3),
+        ErrorKind::InvalidInput => ExitCode::from(4),
+        _ => ExitCode::FAILURE,
+    }
+}
+
+fn run(cli: Cli) -> Result<(), io::Error> {
+    let mut config = Config::load()?;
+    config.color = cli.color.unwrap_or(config.color);
+    let (backend, path) = config.storage_location(cli.file, cli.backend)?;
+    let mut history_path = path.clone().into_os_string();
+    history_path.push(".history.json");
+    let history = History::new(history_path.into(), config.history_depth);
+    let (storage, archive): (Box<dyn Storage>, Box<dyn Storage>) = match backend {
+        Backend::Json => {
+            let mut archive_path = path.clone().into_os_string();
+            archive_path.push(".archive.json");
+            (
+                Box::new(FileStorage::new(path)),
+                Box::new(FileStorage::new(archive_path.into())),
+            )
+        }
+        Backend::Sqlite => (
+            Box::new(SqliteStorage::open(path.clone())?),
+            Box::new(SqliteStorage::open_archive(path)?),
+        ),
+    };
+    let mut todo_list = TodoList::new(storage)?
+        .with_history(history)
+        .with_child_policy(config.child_policy)
+        .with_archive(archive)?;
+    todo_list.purge_expired_trash(config.trash_retention_days)?;
+    todo_list.archive_expired(config.archive_after_days)?;
+
+    match cli.command {
+        Some(command) => cli::run(command, &mut todo_list, &config),
+        None => run_menu(&mut todo_list, &config.layout()),
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            exit_code(&e)
+        }
+    }
+}
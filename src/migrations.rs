@@ -0,0 +1,152 @@
+use std::io::{self, Error, ErrorKind};
+
+use serde_json::{json, Map, Value};
+
+// Version 0 is the original bare `{"<id>": task}` map without any marker.
+pub const CURRENT_VERSION: u64 = 2;
+
+struct Migration {
+    // The version this migration upgrades from; it produces `from + 1`.
+    from: u64,
+    description: &'static str,
+    // Returns how many tasks were touched, for the report.
+    apply: fn(&mut Value) -> Result<usize, io::Error>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 0,
+        description: "wrap the task map in a versioned envelope",
+        apply: wrap_in_envelope,
+    },
+    Migration {
+        from: 1,
+        description: "replace `completed` flags with statuses",
+        apply: completed_to_status,
+    },
+];
+
+fn version_of(document: &Value) -> Result<u64, io::Error> {
+    match document.get("version") {
+        None => Ok(0),
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| invalid(format!("Invalid format version {}", version))),
+    }
+}
+
+// Upgrades `document` in place to `CURRENT_VERSION`, running each pending
+// migration in order, and describes the steps taken.
+pub fn migrate(document: &mut Value) -> Result<Vec<String>, io::Error> {
+    let from = version_of(document)?;
+    if from > CURRENT_VERSION {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            format!(
+                "File format version {} is newer than this program supports ({})",
+                from, CURRENT_VERSION
+            ),
+        ));
+    }
+
+    let mut steps = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|migration| migration.from >= from) {
+        let touched = (migration.apply)(document)?;
+        document["version"] = json!(migration.from + 1);
+        steps.push(format!(
+            "v{} -> v{}: {} ({} task{})",
+            migration.from,
+            migration.from + 1,
+            migration.description,
+            touched,
+            if touched == 1 { "" } else { "s" }
+        ));
+    }
+    Ok(steps)
+}
+
+fn tasks_mut(document: &mut Value) -> Result<&mut Map<String, Value>, io::Error> {
+    document
+        .get_mut("tasks")
+        .and_then(Value::as_object_mut)
+        .ok_or_else(|| invalid("Missing task map".to_string()))
+}
+
+fn wrap_in_envelope(document: &mut Value) -> Result<usize, io::Error> {
+    let tasks = match document.take() {
+        Value::Object(tasks) => tasks,
+        _ => return Err(invalid("Expected a map of tasks".to_string())),
+    };
+    let count = tasks.len();
+    *document = json!({ "version": 0, "tasks": tasks });
+    Ok(count)
+}
+
+fn completed_to_status(document: &mut Value) -> Result<usize, io::Error> {
+    let mut touched = 0;
+    for task in tasks_mut(document)?.values_mut() {
+        let Some(task) = task.as_object_mut() else {
+            continue;
+        };
+        if let Some(completed) = task.remove("completed") {
+            if !task.contains_key("status") {
+                let status = if completed.as_bool() == Some(true) { "done" } else { "todo" };
+                task.insert("status".to_string(), json!(status));
+            }
+            touched += 1;
+        }
+    }
+    Ok(touched)
+}
+
+fn invalid(message: String) -> io::Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_map_with_completed_flags_is_upgraded_to_current() {
+        let mut document = json!({
+            "1": { "id": 1, "description": "Done", "completed": true },
+            "2": { "id": 2, "description": "Open", "completed": false },
+            "3": { "id": 3, "description": "Already migrated", "completed": true, "status": "cancelled" },
+        });
+        let steps = migrate(&mut document).unwrap();
+        assert_eq!(
+            steps,
+            [
+                "v0 -> v1: wrap the task map in a versioned envelope (3 tasks)",
+                "v1 -> v2: replace `completed` flags with statuses (3 tasks)",
+            ]
+        );
+        assert_eq!(
+            document,
+            json!({
+                "version": 2,
+                "tasks": {
+                    "1": { "id": 1, "description": "Done", "status": "done" },
+                    "2": { "id": 2, "description": "Open", "status": "todo" },
+                    "3": { "id": 3, "description": "Already migrated", "status": "cancelled" },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn current_documents_are_left_alone() {
+        let mut document = json!({ "version": CURRENT_VERSION, "tasks": {} });
+        let original = document.clone();
+        assert!(migrate(&mut document).unwrap().is_empty());
+        assert_eq!(document, original);
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        let mut document = json!({ "version": CURRENT_VERSION + 1, "tasks": {} });
+        let error = migrate(&mut document).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Unsupported);
+    }
+}
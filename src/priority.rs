@@ -0,0 +1,66 @@
+use std::fmt;
+use std::io::{self, Error, ErrorKind};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    #[default]
+    None,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Priority {
+    pub fn is_none(&self) -> bool {
+        *self == Priority::None
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Priority::None => "none",
+            Priority::Low => "low",
+            Priority::Medium => "medium",
+            Priority::High => "high",
+            Priority::Critical => "critical",
+        }
+    }
+
+    pub fn marker(&self) -> &'static str {
+        match self {
+            Priority::None => "",
+            Priority::Low => "!",
+            Priority::Medium => "!!",
+            Priority::High => "!!!",
+            Priority::Critical => "!!!!",
+        }
+    }
+}
+
+impl FromStr for Priority {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "none" | "n" | "0" => Ok(Priority::None),
+            "low" | "l" | "1" => Ok(Priority::Low),
+            "medium" | "med" | "m" | "2" => Ok(Priority::Medium),
+            "high" | "h" | "3" => Ok(Priority::High),
+            "critical" | "crit" | "c" | "4" => Ok(Priority::Critical),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid priority '{}' (expected none, low, medium, high or critical)", s),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -0,0 +1,204 @@
+use std::fmt;
+use std::io::{self, Error, ErrorKind};
+use std::str::FromStr;
+
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// When the next occurrence of a recurring task falls due.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recurrence {
+    Daily,
+    // On the given weekdays, Monday first; every seven days when empty.
+    Weekly(Vec<Weekday>),
+    // On the given day of the month, or the last day of shorter months.
+    // Without a day it keeps the previous due date's day, which can drift
+    // earlier after a short month.
+    Monthly(Option<u32>),
+    EveryDays(u64),
+    // Counted from when the previous occurrence was completed, not from its due date.
+    AfterCompletion(u64),
+}
+
+impl Recurrence {
+    // The due date of the occurrence after one due on `due` and completed on
+    // `completed`. Occurrences missed while a task was overdue are skipped,
+    // and tasks without a due date are scheduled from their completion.
+    pub fn next_date(&self, due: Option<NaiveDate>, completed: NaiveDate) -> Option<NaiveDate> {
+        if let Recurrence::AfterCompletion(days) = self {
+            return completed.checked_add_days(Days::new(*days));
+        }
+        let mut date = due.unwrap_or(completed);
+        loop {
+            date = self.step(date)?;
+            if date > completed {
+                return Some(date);
+            }
+        }
+    }
+
+    fn step(&self, date: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Recurrence::Daily => date.checked_add_days(Days::new(1)),
+            Recurrence::Weekly(weekdays) if weekdays.is_empty() => date.checked_add_days(Days::new(7)),
+            Recurrence::Weekly(weekdays) => (1..=7)
+                .filter_map(|ahead| date.checked_add_days(Days::new(ahead)))
+                .find(|next| weekdays.contains(&next.weekday())),
+            Recurrence::Monthly(None) => date.checked_add_months(Months::new(1)),
+            Recurrence::Monthly(Some(day)) => {
+                let first = date.with_day(1)?;
+                [first, first.checked_add_months(Months::new(1))?]
+                    .into_iter()
+                    .filter_map(|month| on_day(month, *day))
+                    .find(|next| *next > date)
+            }
+            Recurrence::EveryDays(days) | Recurrence::AfterCompletion(days) => {
+                date.checked_add_days(Days::new(*days))
+            }
+        }
+    }
+}
+
+// Accepts `daily`, `weekly`, `weekly:mon,thu`, `monthly`, `monthly:15`,
+// `every:3d`/`every:2w` and `after:3d`/`after:2w`.
+impl FromStr for Recurrence {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.trim().to_lowercase();
+        let (rule, argument) = match lower.split_once(':') {
+            Some((rule, argument)) => (rule, Some(argument)),
+            None => (lower.as_str(), None),
+        };
+        let recurrence = match (rule, argument) {
+            ("daily", None) => Some(Recurrence::Daily),
+            ("weekly", None) => Some(Recurrence::Weekly(Vec::new())),
+            ("weekly", Some(days)) => parse_weekdays(days).map(Recurrence::Weekly),
+            ("monthly", None) => Some(Recurrence::Monthly(None)),
+            ("monthly", Some(day)) => day
+                .trim()
+                .parse()
+                .ok()
+                .filter(|day| (1..=31).contains(day))
+                .map(|day| Recurrence::Monthly(Some(day))),
+            ("every", Some(interval)) => parse_interval(interval).map(Recurrence::EveryDays),
+            ("after", Some(interval)) => parse_interval(interval).map(Recurrence::AfterCompletion),
+            _ => None,
+        };
+        recurrence.ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Invalid recurrence '{}' (expected daily, weekly, weekly:mon,thu, monthly, monthly:15, every:3d or after:2w)",
+                    s
+                ),
+            )
+        })
+    }
+}
+
+// `day` of the month that `month` falls in, clamped to its last day.
+fn on_day(month: NaiveDate, day: u32) -> Option<NaiveDate> {
+    (1..=day).rev().find_map(|day| month.with_day(day))
+}
+
+fn parse_weekdays(days: &str) -> Option<Vec<Weekday>> {
+    let mut weekdays = days
+        .split(',')
+        .map(|day| day.trim().parse::<Weekday>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    weekdays.sort_by_key(Weekday::num_days_from_monday);
+    weekdays.dedup();
+    Some(weekdays)
+}
+
+fn parse_interval(interval: &str) -> Option<u64> {
+    let (count, unit) = interval.split_at(interval.len().checked_sub(1)?);
+    let count: u64 = count.parse().ok().filter(|&count| count > 0)?;
+    match unit {
+        "d" => Some(count),
+        "w" => count.checked_mul(7),
+        _ => None,
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Recurrence::Daily => f.write_str("daily"),
+            Recurrence::Weekly(weekdays) if weekdays.is_empty() => f.write_str("weekly"),
+            Recurrence::Weekly(weekdays) => {
+                let days: Vec<String> = weekdays.iter().map(|day| day.to_string().to_lowercase()).collect();
+                write!(f, "weekly:{}", days.join(","))
+            }
+            Recurrence::Monthly(None) => f.write_str("monthly"),
+            Recurrence::Monthly(Some(day)) => write!(f, "monthly:{}", day),
+            Recurrence::EveryDays(days) => write!(f, "every:{}d", days),
+            Recurrence::AfterCompletion(days) => write!(f, "after:{}d", days),
+        }
+    }
+}
+
+impl Serialize for Recurrence {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Recurrence {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn rule(s: &str) -> Recurrence {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn monthly_day_is_clamped_to_short_months() {
+        let monthly = rule("monthly:31");
+        assert_eq!(monthly.next_date(Some(date(2024, 1, 31)), date(2024, 1, 31)), Some(date(2024, 2, 29)));
+        assert_eq!(monthly.next_date(Some(date(2023, 1, 31)), date(2023, 1, 31)), Some(date(2023, 2, 28)));
+        // The clamp does not stick: the month after is back on the 31st.
+        assert_eq!(monthly.next_date(Some(date(2024, 2, 29)), date(2024, 2, 29)), Some(date(2024, 3, 31)));
+        assert_eq!(monthly.next_date(Some(date(2024, 3, 31)), date(2024, 3, 31)), Some(date(2024, 4, 30)));
+    }
+
+    #[test]
+    fn missed_weekly_occurrences_are_skipped() {
+        // Due on Monday the 6th and completed on Wednesday the 15th.
+        let (due, completed) = (Some(date(2024, 5, 6)), date(2024, 5, 15));
+        assert_eq!(rule("weekly").next_date(due, completed), Some(date(2024, 5, 20)));
+        assert_eq!(rule("weekly:mon,thu").next_date(due, completed), Some(date(2024, 5, 16)));
+        // An occurrence due on the day of completion counts as missed too.
+        assert_eq!(rule("weekly").next_date(due, date(2024, 5, 20)), Some(date(2024, 5, 27)));
+    }
+
+    #[test]
+    fn after_counts_from_completion() {
+        let after = rule("after:3d");
+        assert_eq!(after.next_date(Some(date(2024, 5, 1)), date(2024, 5, 10)), Some(date(2024, 5, 13)));
+        assert_eq!(after.next_date(Some(date(2024, 5, 20)), date(2024, 5, 10)), Some(date(2024, 5, 13)));
+        assert_eq!(after.next_date(None, date(2024, 5, 10)), Some(date(2024, 5, 13)));
+        assert_eq!(rule("after:2w"), Recurrence::AfterCompletion(14));
+    }
+
+    #[test]
+    fn rules_round_trip_through_text() {
+        assert_eq!(rule("weekly:thu,mon,thu").to_string(), "weekly:mon,thu");
+        assert_eq!(rule("every:2w").to_string(), "every:14d");
+        for invalid in ["monthly:0", "monthly:32", "every:0d", "after:3", "weekly:someday", "yearly"] {
+            assert!(invalid.parse::<Recurrence>().is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+use std::io::{self, Error, ErrorKind};
+
+use serde::Deserialize;
+
+use crate::filter::Filter;
+use crate::table::Column;
+use crate::SortKey;
+
+// A named listing, defined in the config under `[reports.<name>]`. Reports
+// in the config replace built-in ones of the same name.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Report {
+    // Shown when listing the available reports.
+    pub description: Option<String>,
+    // A filter expression; every task when missing.
+    pub filter: Option<String>,
+    // Sort keys, the first deciding most; by priority when empty.
+    pub sort: Vec<SortKey>,
+    // Table columns; the configured ones when empty.
+    pub columns: Vec<Column>,
+    // The most tasks to show.
+    pub limit: Option<usize>,
+}
+
+impl Report {
+    pub fn filter(&self, name: &str) -> Result<Option<Filter>, io::Error> {
+        self.filter
+            .as_deref()
+            .map(|filter| {
+                Filter::parse(filter)
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, format!("Report '{}': {}", name, e)))
+            })
+            .transpose()
+    }
+
+    pub fn sort(&self) -> &[SortKey] {
+        if self.sort.is_empty() {
+            &[SortKey::Priority]
+        } else {
+            &self.sort
+        }
+    }
+}
+
+pub fn builtin() -> BTreeMap<String, Report> {
+    let report = |description: &str, filter: &str, sort: &[SortKey], limit: Option<usize>| Report {
+        description: Some(description.to_string()),
+        filter: Some(filter.to_string()),
+        sort: sort.to_vec(),
+        columns: Vec::new(),
+        limit,
+    };
+    BTreeMap::from([
+        (
+            "next".to_string(),
+            report(
+                "The ten most pressing tasks that can be worked on now",
+                "is:actionable",
+                &[SortKey::Priority, SortKey::Due],
+                Some(10),
+            ),
+        ),
+        (
+            "overdue".to_string(),
+            report(
+                "Open tasks past their due date, soonest deadline first",
+                "status:open and due<now",
+                &[SortKey::Due],
+                None,
+            ),
+        ),
+        (
+            "done-this-week".to_string(),
+            report(
+                "Tasks completed since Monday, most recent first",
+                "status:done and completed>=week",
+                &[SortKey::Completed],
+                None,
+            ),
+        ),
+    ])
+}
+
+// The built-in reports with the configured ones added or replacing them.
+pub fn all(configured: &BTreeMap<String, Report>) -> BTreeMap<String, Report> {
+    let mut reports = builtin();
+    reports.extend(configured.iter().map(|(name, report)| (name.clone(), report.clone())));
+    reports
+}
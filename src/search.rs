@@ -0,0 +1,182 @@
+use std::io::{self, Error, ErrorKind};
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::color::Style;
+use crate::Task;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Mode {
+    // The text appears anywhere.
+    #[default]
+    Substring,
+    // The text appears as whole words.
+    Word,
+    // A regular expression matches.
+    Regex,
+    // The characters appear in order, not necessarily next to each other.
+    Fuzzy,
+}
+
+pub struct Search {
+    matcher: Matcher,
+}
+
+enum Matcher {
+    Pattern(Regex),
+    Fuzzy(Vec<char>),
+}
+
+// Where a search matched one task, as byte ranges into its description and
+// notes. Higher scores are better matches; only fuzzy matches differ.
+pub struct Hit<'a> {
+    pub task: &'a Task,
+    pub score: i64,
+    pub description: Vec<Range<usize>>,
+    pub notes: Vec<Range<usize>>,
+}
+
+impl Search {
+    // Every mode ignores case.
+    pub fn new(query: &str, mode: Mode) -> Result<Self, io::Error> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "Search text cannot be empty"));
+        }
+        let pattern = match mode {
+            Mode::Substring => regex::escape(query),
+            Mode::Word => format!(r"\b{}\b", regex::escape(query)),
+            Mode::Regex => query.to_string(),
+            Mode::Fuzzy => {
+                let chars = query.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_lowercase);
+                return Ok(Search {
+                    matcher: Matcher::Fuzzy(chars.collect()),
+                });
+            }
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("Invalid pattern: {}", e)))?;
+        Ok(Search {
+            matcher: Matcher::Pattern(regex),
+        })
+    }
+
+    pub fn hit<'a>(&self, task: &'a Task) -> Option<Hit<'a>> {
+        let description = self.find(task.description.get());
+        let notes = task.notes.as_deref().and_then(|notes| self.find(notes));
+        if description.is_none() && notes.is_none() {
+            return None;
+        }
+        let (description_score, description) = description.unwrap_or_default();
+        let (notes_score, notes) = notes.unwrap_or_default();
+        Some(Hit {
+            task,
+            // A description match counts for more than the same match in the notes.
+            score: (description_score * 2).max(notes_score),
+            description,
+            notes,
+        })
+    }
+
+    // Searches `tasks`, best matches first and ties in ID order.
+    pub fn run<'a>(&self, tasks: impl IntoIterator<Item = &'a Task>) -> Vec<Hit<'a>> {
+        let mut hits: Vec<Hit> = tasks.into_iter().filter_map(|task| self.hit(task)).collect();
+        hits.sort_by_key(|hit| (std::cmp::Reverse(hit.score), hit.task.id.0));
+        hits
+    }
+
+    fn find(&self, text: &str) -> Option<(i64, Vec<Range<usize>>)> {
+        match &self.matcher {
+            Matcher::Pattern(regex) => {
+                let ranges: Vec<Range<usize>> = regex
+                    .find_iter(text)
+                    .map(|found| found.range())
+                    .filter(|range| !range.is_empty())
+                    .collect();
+                (!ranges.is_empty()).then_some((0, ranges))
+            }
+            Matcher::Fuzzy(query) => fuzzy_match(query, text),
+        }
+    }
+}
+
+// Matches `query` as a subsequence of `text`, taking each character at its
+// first chance. Runs of adjacent characters and characters starting a word
+// score extra, and every character skipped in between costs a little.
+fn fuzzy_match(query: &[char], text: &str) -> Option<(i64, Vec<Range<usize>>)> {
+    let mut wanted = query.iter().peekable();
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut score = 0;
+    let mut previous: Option<char> = None;
+    let mut gap = 0;
+    for (index, c) in text.char_indices() {
+        let Some(&&next) = wanted.peek() else {
+            break;
+        };
+        if !c.to_lowercase().eq(std::iter::once(next)) {
+            if !ranges.is_empty() {
+                gap += 1;
+            }
+            previous = Some(c);
+            continue;
+        }
+        wanted.next();
+        score += 1;
+        if previous.is_none_or(|previous| !previous.is_alphanumeric()) {
+            score += 8;
+        }
+        let end = index + c.len_utf8();
+        match ranges.last_mut() {
+            Some(last) if last.end == index => {
+                last.end = end;
+                score += 5;
+            }
+            Some(_) => {
+                score -= gap.min(5);
+                ranges.push(index..end);
+            }
+            None => ranges.push(index..end),
+        }
+        gap = 0;
+        previous = Some(c);
+    }
+    wanted.peek().is_none().then_some((score, ranges))
+}
+
+// Marks the ranges in `text` with `style`, or leaves it plain without one.
+pub fn highlight(text: &str, ranges: &[Range<usize>], style: Option<&Style>) -> String {
+    let Some(style) = style else {
+        return text.to_string();
+    };
+    let mut highlighted = String::with_capacity(text.len() + ranges.len() * 8);
+    let mut end = 0;
+    for range in ranges {
+        highlighted.push_str(&text[end..range.start]);
+        highlighted.push_str(&style.paint(&text[range.clone()]));
+        end = range.end;
+    }
+    highlighted.push_str(&text[end..]);
+    highlighted
+}
+
+// The lines of `text` that contain a match, each highlighted.
+pub fn matching_lines(text: &str, ranges: &[Range<usize>], style: Option<&Style>) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for line in text.split_inclusive('\n') {
+        let end = start + line.len();
+        let in_line: Vec<Range<usize>> = ranges
+            .iter()
+            .filter(|range| range.start < end && range.end > start)
+            .map(|range| range.start.max(start) - start..range.end.min(end) - start)
+            .collect();
+        if !in_line.is_empty() {
+            lines.push(highlight(line, &in_line, style).trim_end().to_string());
+        }
+        start = end;
+    }
+    lines
+}
@@ -0,0 +1,450 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, Row};
+
+use crate::due::Due;
+use crate::recur::Recurrence;
+use crate::{lock_file, Storage, StorageLock, StorageOp, Task, TaskDescription, TaskId};
+
+// Schema migrations with what each does, applied in order; the database's
+// `user_version` records how many have run. Reads see an outdated database
+// upgraded only for their duration, and the first write or `migrate` keeps
+// the upgrade, as with the JSON format.
+const MIGRATIONS: &[(&str, &str)] = &[
+    (
+        "Create the task and tag tables",
+        "CREATE TABLE IF NOT EXISTS tasks (
+             id           INTEGER PRIMARY KEY,
+             description  TEXT NOT NULL,
+             status       TEXT NOT NULL DEFAULT 'todo',
+             priority     TEXT NOT NULL DEFAULT 'none',
+             due          TEXT,
+             notes        TEXT,
+             created_at   TEXT,
+             modified_at  TEXT,
+             completed_at TEXT
+         );
+         CREATE TABLE IF NOT EXISTS task_tags (
+             task_id INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+             tag     TEXT NOT NULL,
+             PRIMARY KEY (task_id, tag)
+         );
+         CREATE INDEX IF NOT EXISTS task_tags_by_tag ON task_tags(tag);",
+    ),
+    (
+        "Add deletion timestamps for the trash",
+        "ALTER TABLE tasks ADD COLUMN deleted_at TEXT;",
+    ),
+    (
+        "Add the archive tables",
+        "CREATE TABLE IF NOT EXISTS archived_tasks (
+             id           INTEGER PRIMARY KEY,
+             description  TEXT NOT NULL,
+             status       TEXT NOT NULL DEFAULT 'todo',
+             priority     TEXT NOT NULL DEFAULT 'none',
+             due          TEXT,
+             notes        TEXT,
+             created_at   TEXT,
+             modified_at  TEXT,
+             completed_at TEXT,
+             deleted_at   TEXT
+         );
+         CREATE TABLE IF NOT EXISTS archived_task_tags (
+             task_id INTEGER NOT NULL REFERENCES archived_tasks(id) ON DELETE CASCADE,
+             tag     TEXT NOT NULL,
+             PRIMARY KEY (task_id, tag)
+         );",
+    ),
+    (
+        "Add parent columns for subtasks",
+        "ALTER TABLE tasks ADD COLUMN parent INTEGER;
+         ALTER TABLE archived_tasks ADD COLUMN parent INTEGER;",
+    ),
+    (
+        "Add the dependency tables",
+        "CREATE TABLE IF NOT EXISTS task_dependencies (
+             task_id    INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+             depends_on INTEGER NOT NULL,
+             PRIMARY KEY (task_id, depends_on)
+         );
+         CREATE TABLE IF NOT EXISTS archived_task_dependencies (
+             task_id    INTEGER NOT NULL REFERENCES archived_tasks(id) ON DELETE CASCADE,
+             depends_on INTEGER NOT NULL,
+             PRIMARY KEY (task_id, depends_on)
+         );",
+    ),
+    (
+        "Add recurrence and series columns",
+        "ALTER TABLE tasks ADD COLUMN recurrence TEXT;
+         ALTER TABLE tasks ADD COLUMN series INTEGER;
+         ALTER TABLE archived_tasks ADD COLUMN recurrence TEXT;
+         ALTER TABLE archived_tasks ADD COLUMN series INTEGER;",
+    ),
+];
+
+// The tables a storage reads and writes. The archive lives in the same
+// database as the list, with its own tables and lock.
+struct Tables {
+    tasks: &'static str,
+    tags: &'static str,
+    dependencies: &'static str,
+    lock_suffix: &'static str,
+    // Whether `migrate` reports the shared schema's steps through this storage.
+    owns_schema: bool,
+}
+
+const LIST_TABLES: Tables = Tables {
+    tasks: "tasks",
+    tags: "task_tags",
+    dependencies: "task_dependencies",
+    lock_suffix: ".lock",
+    owns_schema: true,
+};
+
+const ARCHIVE_TABLES: Tables = Tables {
+    tasks: "archived_tasks",
+    tags: "archived_task_tags",
+    dependencies: "archived_task_dependencies",
+    lock_suffix: ".archive.lock",
+    owns_schema: false,
+};
+
+pub struct SqliteStorage {
+    path: PathBuf,
+    conn: Connection,
+    tables: &'static Tables,
+}
+
+impl SqliteStorage {
+    pub fn open(path: PathBuf) -> Result<Self, io::Error> {
+        Self::open_tables(path, &LIST_TABLES)
+    }
+
+    pub fn open_archive(path: PathBuf) -> Result<Self, io::Error> {
+        Self::open_tables(path, &ARCHIVE_TABLES)
+    }
+
+    fn open_tables(path: PathBuf, tables: &'static Tables) -> Result<Self, io::Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(&path).map_err(sql_error)?;
+        conn.execute_batch("PRAGMA foreign_keys = ON;").map_err(sql_error)?;
+        Ok(SqliteStorage { path, conn, tables })
+    }
+
+    // How many of `MIGRATIONS` the database has had.
+    fn schema_version(&self) -> Result<usize, io::Error> {
+        let version: i64 = self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(sql_error)?;
+        Ok(usize::try_from(version).unwrap_or(0))
+    }
+
+    // Runs the migrations after `version` in the caller's transaction.
+    fn run_migrations(&self, version: usize) -> Result<(), io::Error> {
+        for (version, (_, migration)) in (1..).zip(MIGRATIONS).skip(version) {
+            self.conn.execute_batch(migration).map_err(sql_error)?;
+            self.conn.pragma_update(None, "user_version", version).map_err(sql_error)?;
+        }
+        Ok(())
+    }
+
+    // Brings the schema up to date for good, before a write.
+    fn upgrade(&self) -> Result<(), io::Error> {
+        let version = self.schema_version()?;
+        if version >= MIGRATIONS.len() {
+            return Ok(());
+        }
+        let tx = self.conn.unchecked_transaction().map_err(sql_error)?;
+        self.run_migrations(version)?;
+        tx.commit().map_err(sql_error)
+    }
+
+    // Runs `read` against an up-to-date schema. An outdated database is
+    // upgraded inside a transaction that is rolled back afterwards, so
+    // reading never changes the file.
+    fn reading<T>(&self, read: impl FnOnce() -> Result<T, io::Error>) -> Result<T, io::Error> {
+        let version = self.schema_version()?;
+        if version >= MIGRATIONS.len() {
+            return read();
+        }
+        let _tx = self.conn.unchecked_transaction().map_err(sql_error)?;
+        self.run_migrations(version)?;
+        read()
+    }
+
+    // Savepoints nest, so single-task writes stay atomic both on their own
+    // and inside the transaction opened by `apply`.
+    fn atomically(&self, write: impl FnOnce() -> Result<(), io::Error>) -> Result<(), io::Error> {
+        self.conn.execute_batch("SAVEPOINT task_write").map_err(sql_error)?;
+        match write() {
+            Ok(()) => self.conn.execute_batch("RELEASE task_write").map_err(sql_error),
+            Err(e) => {
+                let _ = self.conn.execute_batch("ROLLBACK TO task_write; RELEASE task_write");
+                Err(e)
+            }
+        }
+    }
+
+    fn read_tasks(&self) -> Result<HashMap<usize, Task>, io::Error> {
+        let mut tasks = HashMap::new();
+        let mut select = self
+            .conn
+            .prepare(&format!(
+                "SELECT id, description, status, priority, due, notes,
+                        created_at, modified_at, completed_at, deleted_at, parent,
+                        recurrence, series
+                 FROM {}",
+                self.tables.tasks
+            ))
+            .map_err(sql_error)?;
+        let mut rows = select.query([]).map_err(sql_error)?;
+        while let Some(row) = rows.next().map_err(sql_error)? {
+            let task = Self::read_task(row)?;
+            tasks.insert(task.id.0, task);
+        }
+
+        let mut select_tags = self
+            .conn
+            .prepare(&format!("SELECT task_id, tag FROM {}", self.tables.tags))
+            .map_err(sql_error)?;
+        let tags = select_tags
+            .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))
+            .map_err(sql_error)?;
+        for tag in tags {
+            let (task_id, tag) = tag.map_err(sql_error)?;
+            if let Some(task) = usize::try_from(task_id).ok().and_then(|id| tasks.get_mut(&id)) {
+                task.tags.insert(tag);
+            }
+        }
+
+        let mut select_dependencies = self
+            .conn
+            .prepare(&format!("SELECT task_id, depends_on FROM {}", self.tables.dependencies))
+            .map_err(sql_error)?;
+        let dependencies = select_dependencies
+            .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))
+            .map_err(sql_error)?;
+        for dependency in dependencies {
+            let (task_id, prerequisite) = dependency.map_err(sql_error)?;
+            let prerequisite = usize::try_from(prerequisite).map_err(|e| invalid(task_id, e))?;
+            if let Some(task) = usize::try_from(task_id).ok().and_then(|id| tasks.get_mut(&id)) {
+                task.depends_on.insert(prerequisite);
+            }
+        }
+        Ok(tasks)
+    }
+
+    fn read_task(row: &Row) -> Result<Task, io::Error> {
+        let get_text = |index: usize| row.get::<_, Option<String>>(index).map_err(sql_error);
+        let id: i64 = row.get(0).map_err(sql_error)?;
+        let get_id = |index: usize| -> Result<Option<usize>, io::Error> {
+            let value: Option<i64> = row.get(index).map_err(sql_error)?;
+            value.map(|value| usize::try_from(value).map_err(|e| invalid(id, e))).transpose()
+        };
+        let description: String = row.get(1).map_err(sql_error)?;
+        let status: String = row.get(2).map_err(sql_error)?;
+        let priority: String = row.get(3).map_err(sql_error)?;
+
+        Ok(Task {
+            id: TaskId(usize::try_from(id).map_err(|e| invalid(id, e))?),
+            description: TaskDescription(description),
+            status: status.parse()?,
+            priority: priority.parse()?,
+            due: get_text(4)?
+                .map(|due| Due::decode(&due).map_err(|e| invalid(id, e)))
+                .transpose()?,
+            tags: BTreeSet::new(),
+            depends_on: BTreeSet::new(),
+            notes: get_text(5)?,
+            created_at: parse_timestamp(id, get_text(6)?)?,
+            modified_at: parse_timestamp(id, get_text(7)?)?,
+            completed_at: parse_timestamp(id, get_text(8)?)?,
+            deleted_at: parse_timestamp(id, get_text(9)?)?,
+            parent: get_id(10)?,
+            recurrence: get_text(11)?
+                .map(|rule| rule.parse::<Recurrence>().map_err(|e| invalid(id, e)))
+                .transpose()?,
+            series: get_id(12)?,
+        })
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn save(&self, tasks: &HashMap<usize, Task>) -> Result<(), io::Error> {
+        self.upgrade()?;
+        let tx = self.conn.unchecked_transaction().map_err(sql_error)?;
+        tx.execute(&format!("DELETE FROM {}", self.tables.tasks), [])
+            .map_err(sql_error)?;
+        for task in tasks.values() {
+            self.upsert(task)?;
+        }
+        tx.commit().map_err(sql_error)
+    }
+
+    fn apply(&self, changes: &[StorageOp]) -> Result<(), io::Error> {
+        self.upgrade()?;
+        let tx = self.conn.unchecked_transaction().map_err(sql_error)?;
+        for change in changes {
+            match change {
+                StorageOp::Upsert(task) => self.upsert(task)?,
+                StorageOp::Delete(id) => self.delete(*id)?,
+            }
+        }
+        tx.commit().map_err(sql_error)
+    }
+
+    fn upsert(&self, task: &Task) -> Result<(), io::Error> {
+        self.upgrade()?;
+        let id = task.id.0 as i64;
+        let Tables {
+            tasks,
+            tags,
+            dependencies,
+            ..
+        } = self.tables;
+        self.atomically(|| {
+            self.conn
+                .prepare_cached(&format!(
+                    "INSERT INTO {} (id, description, status, priority, due, notes,
+                                     created_at, modified_at, completed_at, deleted_at, parent,
+                                     recurrence, series)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+                     ON CONFLICT (id) DO UPDATE SET
+                         description = excluded.description,
+                         status = excluded.status,
+                         priority = excluded.priority,
+                         due = excluded.due,
+                         notes = excluded.notes,
+                         created_at = excluded.created_at,
+                         modified_at = excluded.modified_at,
+                         completed_at = excluded.completed_at,
+                         deleted_at = excluded.deleted_at,
+                         parent = excluded.parent,
+                         recurrence = excluded.recurrence,
+                         series = excluded.series",
+                    tasks
+                ))
+                .and_then(|mut upsert| {
+                    upsert.execute(params![
+                        id,
+                        task.description.get(),
+                        task.status.name(),
+                        task.priority.name(),
+                        task.due.map(|due| due.encode()),
+                        task.notes,
+                        task.created_at.map(|at| at.to_rfc3339()),
+                        task.modified_at.map(|at| at.to_rfc3339()),
+                        task.completed_at.map(|at| at.to_rfc3339()),
+                        task.deleted_at.map(|at| at.to_rfc3339()),
+                        task.parent.map(|parent| parent as i64),
+                        task.recurrence.as_ref().map(|rule| rule.to_string()),
+                        task.series.map(|series| series as i64),
+                    ])
+                })
+                .map_err(sql_error)?;
+
+            self.conn
+                .execute(&format!("DELETE FROM {} WHERE task_id = ?1", tags), [id])
+                .map_err(sql_error)?;
+            let mut insert_tag = self
+                .conn
+                .prepare_cached(&format!("INSERT INTO {} (task_id, tag) VALUES (?1, ?2)", tags))
+                .map_err(sql_error)?;
+            for tag in &task.tags {
+                insert_tag.execute(params![id, tag]).map_err(sql_error)?;
+            }
+
+            self.conn
+                .execute(&format!("DELETE FROM {} WHERE task_id = ?1", dependencies), [id])
+                .map_err(sql_error)?;
+            let mut insert_dependency = self
+                .conn
+                .prepare_cached(&format!(
+                    "INSERT INTO {} (task_id, depends_on) VALUES (?1, ?2)",
+                    dependencies
+                ))
+                .map_err(sql_error)?;
+            for &prerequisite in &task.depends_on {
+                insert_dependency
+                    .execute(params![id, prerequisite as i64])
+                    .map_err(sql_error)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn delete(&self, id: usize) -> Result<(), io::Error> {
+        self.upgrade()?;
+        self.conn
+            .execute(&format!("DELETE FROM {} WHERE id = ?1", self.tables.tasks), [id as i64])
+            .map_err(sql_error)?;
+        Ok(())
+    }
+
+    fn load(&self) -> Result<HashMap<usize, Task>, io::Error> {
+        self.reading(|| self.read_tasks())
+    }
+
+    fn max_id(&self) -> Result<Option<usize>, io::Error> {
+        self.reading(|| {
+            let max: Option<i64> = self
+                .conn
+                .query_row(&format!("SELECT MAX(id) FROM {}", self.tables.tasks), [], |row| row.get(0))
+                .map_err(sql_error)?;
+            Ok(max.and_then(|id| usize::try_from(id).ok()))
+        })
+    }
+
+    // The list and archive share one schema, so only the list reports its steps.
+    fn migrate(&self, dry_run: bool) -> Result<Vec<String>, io::Error> {
+        if !self.tables.owns_schema {
+            return Ok(Vec::new());
+        }
+        let version = self.schema_version()?;
+        let steps = MIGRATIONS
+            .iter()
+            .skip(version)
+            .map(|(step, _)| step.to_string())
+            .collect();
+        if !dry_run {
+            self.upgrade()?;
+        }
+        Ok(steps)
+    }
+
+    // SQLite serialises its own writes, but `TodoList` reloads and rewrites
+    // under this lock, so it has to be shared with other sessions too.
+    fn lock(&self) -> Result<StorageLock, io::Error> {
+        let mut lock_path = self.path.clone().into_os_string();
+        lock_path.push(self.tables.lock_suffix);
+        lock_file(&PathBuf::from(lock_path))
+    }
+}
+
+fn parse_timestamp(id: i64, value: Option<String>) -> Result<Option<DateTime<Utc>>, io::Error> {
+    value
+        .map(|value| {
+            DateTime::parse_from_rfc3339(&value)
+                .map(|at| at.with_timezone(&Utc))
+                .map_err(|e| invalid(id, e))
+        })
+        .transpose()
+}
+
+fn invalid(id: i64, error: impl std::fmt::Display) -> io::Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("Invalid data for task {}: {}", id, error),
+    )
+}
+
+fn sql_error(error: rusqlite::Error) -> io::Error {
+    Error::other(error)
+}
@@ -0,0 +1,86 @@
+use std::fmt;
+use std::io::{self, Error, ErrorKind};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    #[default]
+    Todo,
+    InProgress,
+    Blocked,
+    Done,
+    Cancelled,
+}
+
+impl Status {
+    pub fn is_open(&self) -> bool {
+        matches!(self, Status::Todo | Status::InProgress | Status::Blocked)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Status::Todo => "todo",
+            Status::InProgress => "in-progress",
+            Status::Blocked => "blocked",
+            Status::Done => "done",
+            Status::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn marker(&self) -> &'static str {
+        match self {
+            Status::Todo => " ",
+            Status::InProgress => ">",
+            Status::Blocked => "#",
+            Status::Done => "✓",
+            Status::Cancelled => "✗",
+        }
+    }
+
+    // Closed tasks can only be reopened; open tasks can move to any other state.
+    pub fn transition(self, to: Status) -> Result<Status, io::Error> {
+        if self == to {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Task is already {}", self),
+            ));
+        }
+        if !self.is_open() && to != Status::Todo {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Cannot change a {} task to {}; reopen it first", self, to),
+            ));
+        }
+        Ok(to)
+    }
+}
+
+impl FromStr for Status {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "todo" | "open" => Ok(Status::Todo),
+            "in-progress" | "inprogress" | "started" | "doing" => Ok(Status::InProgress),
+            "blocked" => Ok(Status::Blocked),
+            "done" | "completed" => Ok(Status::Done),
+            "cancelled" | "canceled" => Ok(Status::Cancelled),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Invalid status '{}' (expected todo, in-progress, blocked, done or cancelled)",
+                    s
+                ),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
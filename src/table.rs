@@ -0,0 +1,308 @@
+use std::io::{self, IsTerminal};
+
+use chrono::{DateTime, Local, Utc};
+use serde::Deserialize;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::age;
+use crate::color::{Style, Theme};
+use crate::Task;
+
+const SEPARATOR: &str = "  ";
+// Below this the description column stays wider than the terminal allows.
+const MIN_DESCRIPTION_WIDTH: usize = 20;
+// Used when stdout is a terminal that does not report its size.
+const DEFAULT_WIDTH: usize = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    Id,
+    Status,
+    Priority,
+    Due,
+    Tags,
+    Age,
+    Description,
+}
+
+pub const DEFAULT_COLUMNS: &[Column] = &[
+    Column::Id,
+    Column::Status,
+    Column::Priority,
+    Column::Due,
+    Column::Tags,
+    Column::Age,
+    Column::Description,
+];
+
+// What happens to descriptions too long for the terminal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Overflow {
+    // Continue on the following lines.
+    #[default]
+    Wrap,
+    // Cut off with an ellipsis.
+    Truncate,
+}
+
+// How task listings are shown: as a table of `columns` fitted to the
+// terminal, or as plain lines when `width` is `None` because stdout is not
+// a terminal. Without a theme nothing is colored.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    pub columns: Vec<Column>,
+    pub overflow: Overflow,
+    pub width: Option<usize>,
+    pub theme: Option<Theme>,
+}
+
+impl Layout {
+    pub fn new(columns: Vec<Column>, overflow: Overflow, theme: Option<Theme>) -> Self {
+        let width = io::stdout().is_terminal().then(|| {
+            terminal_size::terminal_size()
+                .map(|(width, _)| usize::from(width.0))
+                .or_else(|| std::env::var("COLUMNS").ok()?.parse().ok())
+                .unwrap_or(DEFAULT_WIDTH)
+        });
+        Layout {
+            columns,
+            overflow,
+            width,
+            theme,
+        }
+    }
+
+    pub fn with_columns(&self, columns: &[Column]) -> Self {
+        Layout {
+            columns: columns.to_vec(),
+            ..self.clone()
+        }
+    }
+}
+
+// A task as listed, with what the listing knows about its neighbours.
+pub struct Row<'a> {
+    pub task: &'a Task,
+    pub depth: usize,
+    pub progress: Option<(usize, usize)>,
+    // Open prerequisites holding the task up.
+    pub waiting: Vec<usize>,
+}
+
+impl Column {
+    pub fn header(&self) -> &'static str {
+        match self {
+            Column::Id => "ID",
+            Column::Status => "Status",
+            Column::Priority => "Priority",
+            Column::Due => "Due",
+            Column::Tags => "Tags",
+            Column::Age => "Age",
+            Column::Description => "Description",
+        }
+    }
+
+    // Closed tasks are styled the same throughout; otherwise the status,
+    // priority and tags have their own styles and the rest of the row is
+    // styled by when the task is due.
+    fn style<'t>(&self, theme: &'t Theme, row: &Row, now: DateTime<Local>) -> &'t Style {
+        let task = row.task;
+        let own = match self {
+            _ if !task.status.is_open() => None,
+            Column::Status => Some(theme.status(task.status, !row.waiting.is_empty())),
+            Column::Priority => Some(theme.priority(task.priority)),
+            Column::Tags => Some(&theme.tags),
+            _ => None,
+        };
+        own.filter(|style| !style.is_empty()).unwrap_or_else(|| theme.task(task, now))
+    }
+
+    fn cell(&self, row: &Row, now: DateTime<Local>) -> String {
+        let task = row.task;
+        match self {
+            Column::Id => task.id.0.to_string(),
+            // Tasks waiting on others count as blocked, whatever their own status.
+            Column::Status if !row.waiting.is_empty() && task.status.is_open() => "waiting".to_string(),
+            Column::Status => task.status.to_string(),
+            Column::Priority if task.priority.is_none() => String::new(),
+            Column::Priority => task.priority.to_string(),
+            Column::Due => task.due.map(|due| due.to_string()).unwrap_or_default(),
+            Column::Tags => {
+                let tags: Vec<String> = task.tags.iter().map(|tag| format!("+{}", tag)).collect();
+                tags.join(" ")
+            }
+            Column::Age => task
+                .created_at
+                .map(|created_at| age::format_age(created_at, now.with_timezone(&Utc)))
+                .unwrap_or_default(),
+            Column::Description => match row.progress {
+                Some((done, total)) => format!("{} ({}/{} done)", task.description.get(), done, total),
+                None => task.description.get().to_string(),
+            },
+        }
+    }
+}
+
+// Lays `rows` out under a header, each column as wide as its widest cell.
+// When the table is wider than `width`, the description column gives way,
+// wrapping or truncating as the layout says; subtasks are indented in it.
+pub fn render(rows: &[Row], layout: &Layout, width: usize) -> Vec<String> {
+    let now = Local::now();
+    let columns = &layout.columns;
+    let theme = layout.theme.as_ref();
+    let description = columns.iter().position(|&column| column == Column::Description);
+    let header: Vec<String> = columns.iter().map(|column| column.header().to_string()).collect();
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            columns
+                .iter()
+                .map(|column| match column {
+                    Column::Description => format!("{}{}", "  ".repeat(row.depth), column.cell(row, now)),
+                    column => column.cell(row, now),
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut widths: Vec<usize> = (0..columns.len())
+        .map(|i| {
+            std::iter::once(&header)
+                .chain(&cells)
+                .map(|row| row[i].width())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    if let Some(i) = description {
+        let others: usize = widths.iter().enumerate().filter(|&(j, _)| j != i).map(|(_, w)| w).sum();
+        let available = width.saturating_sub(others + SEPARATOR.len() * (columns.len() - 1));
+        widths[i] = widths[i].min(available.max(MIN_DESCRIPTION_WIDTH));
+    }
+
+    let header_styles = theme.map(|theme| vec![&theme.header; columns.len()]);
+    let mut lines = vec![join(&header, &widths, header_styles.as_deref())];
+    for (row, mut cells) in rows.iter().zip(cells) {
+        let styles: Option<Vec<&Style>> =
+            theme.map(|theme| columns.iter().map(|column| column.style(theme, row, now)).collect());
+        let styles = styles.as_deref();
+        let Some(i) = description.filter(|&i| cells[i].width() > widths[i]) else {
+            lines.push(join(&cells, &widths, styles));
+            continue;
+        };
+        let indent = "  ".repeat(row.depth);
+        let text = cells[i].split_off(indent.len());
+        let room = widths[i].saturating_sub(indent.len()).max(1);
+        let fitted = match layout.overflow {
+            Overflow::Truncate => vec![truncate(&text, room)],
+            Overflow::Wrap => wrap(&text, room),
+        };
+        for (n, part) in fitted.into_iter().enumerate() {
+            // Continuation lines leave the other columns empty.
+            if n > 0 {
+                cells.iter_mut().for_each(String::clear);
+            }
+            cells[i] = format!("{}{}", indent, part);
+            lines.push(join(&cells, &widths, styles));
+        }
+    }
+    lines
+}
+
+// Pads each cell to its column's width, styling only the text.
+fn join(cells: &[String], widths: &[usize], styles: Option<&[&Style]>) -> String {
+    let padded: Vec<String> = cells
+        .iter()
+        .zip(widths)
+        .enumerate()
+        .map(|(i, (cell, &width))| {
+            let padding = " ".repeat(width.saturating_sub(cell.width()));
+            match styles {
+                Some(styles) => format!("{}{}", styles[i].paint(cell), padding),
+                None => format!("{}{}", cell, padding),
+            }
+        })
+        .collect();
+    padded.join(SEPARATOR).trim_end().to_string()
+}
+
+// Breaks `text` into lines no wider than `width`, between words where it can.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let needed = if line.is_empty() { word.width() } else { line.width() + 1 + word.width() };
+        if needed <= width {
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        // Words longer than a whole line are split wherever they have to be.
+        for c in word.chars() {
+            if line.width() + c.width().unwrap_or(0) > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            line.push(c);
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    let mut truncated = String::new();
+    for c in text.chars() {
+        if truncated.width() + c.width().unwrap_or(0) + 1 > width {
+            break;
+        }
+        truncated.push(c);
+    }
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_breaks_between_words() {
+        assert_eq!(wrap("buy milk and eggs", 8), ["buy milk", "and eggs"]);
+        assert_eq!(wrap("buy  milk", 20), ["buy milk"]);
+        assert_eq!(wrap("", 5), [""]);
+    }
+
+    #[test]
+    fn wrap_splits_words_longer_than_a_line() {
+        assert_eq!(wrap("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+        assert_eq!(wrap("ab cdefgh", 4), ["ab", "cdef", "gh"]);
+    }
+
+    #[test]
+    fn wrap_measures_display_width() {
+        assert_eq!(wrap("日本語", 4), ["日本", "語"]);
+        assert_eq!(wrap("日本 語", 5), ["日本", "語"]);
+    }
+
+    #[test]
+    fn truncate_leaves_room_for_the_ellipsis() {
+        assert_eq!(truncate("hello world", 6), "hello…");
+        assert_eq!(truncate("日本語", 4), "日…");
+        assert_eq!(truncate("abc", 1), "…");
+    }
+
+    #[test]
+    fn join_pads_cells_without_trailing_space() {
+        let cells = ["a".to_string(), "日".to_string(), String::new()];
+        assert_eq!(join(&cells, &[3, 4, 2], None), "a    日");
+    }
+}
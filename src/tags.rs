@@ -0,0 +1,74 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Error, ErrorKind};
+
+use crate::Task;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TagCount {
+    pub open: usize,
+    pub completed: usize,
+}
+
+// Splits `+tag` words out of a description, so "call mom +home" becomes
+// ("call mom", {"home"}). A lone "+" is kept as text.
+pub fn extract_tags(description: &str) -> (String, BTreeSet<String>) {
+    let mut tags = BTreeSet::new();
+    let mut words = Vec::new();
+    for word in description.split_whitespace() {
+        match word.strip_prefix('+') {
+            Some(tag) if !tag.is_empty() => {
+                tags.insert(tag.to_string());
+            }
+            _ => words.push(word),
+        }
+    }
+    (words.join(" "), tags)
+}
+
+pub fn parse_tag(input: &str) -> Result<String, io::Error> {
+    let tag = input.trim();
+    let tag = tag.strip_prefix('+').unwrap_or(tag);
+    if tag.is_empty() || tag.chars().any(char::is_whitespace) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid tag '{}'", input),
+        ));
+    }
+    Ok(tag.to_string())
+}
+
+pub fn matches(task: &Task, include: &[String], exclude: &[String]) -> bool {
+    include.iter().all(|tag| task.tags.contains(tag))
+        && !exclude.iter().any(|tag| task.tags.contains(tag))
+}
+
+pub fn tag_counts<'a>(tasks: impl IntoIterator<Item = &'a Task>) -> BTreeMap<&'a str, TagCount> {
+    let mut counts: BTreeMap<&str, TagCount> = BTreeMap::new();
+    for task in tasks {
+        for tag in &task.tags {
+            let count = counts.entry(tag).or_default();
+            if !task.status.is_open() {
+                count.completed += 1;
+            } else {
+                count.open += 1;
+            }
+        }
+    }
+    counts
+}
+
+// Applies `+tag`/`-tag` edits; a bare word is treated as `+word`.
+pub fn apply_changes(tags: &BTreeSet<String>, changes: &[String]) -> Result<BTreeSet<String>, io::Error> {
+    let mut tags = tags.clone();
+    for change in changes {
+        match change.strip_prefix('-') {
+            Some(tag) => {
+                tags.remove(&parse_tag(tag)?);
+            }
+            None => {
+                tags.insert(parse_tag(change)?);
+            }
+        }
+    }
+    Ok(tags)
+}
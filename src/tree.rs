@@ -0,0 +1,99 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Error, ErrorKind};
+
+use serde::Deserialize;
+
+use crate::status::Status;
+use crate::Task;
+
+// What happens to the subtasks of a task that is closed or deleted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ChildPolicy {
+    // Refuse while the task still has subtasks in the way.
+    #[default]
+    Block,
+    // Close or delete the subtasks along with their parent.
+    Cascade,
+    // Move the subtasks up to their grandparent, or to the top level.
+    Reparent,
+}
+
+pub fn children<'a>(tasks: impl IntoIterator<Item = &'a Task>, id: usize) -> Vec<&'a Task> {
+    let mut children: Vec<&Task> = tasks
+        .into_iter()
+        .filter(|task| task.parent == Some(id) && !task.is_trashed())
+        .collect();
+    children.sort_by_key(|task| task.id.0);
+    children
+}
+
+// Checks that `parent` can hold `id`: it has to be a live task that is not
+// `id` itself or one of its subtasks.
+pub fn check_parent(tasks: &HashMap<usize, Task>, id: usize, parent: usize) -> Result<(), io::Error> {
+    if tasks.get(&parent).is_none_or(|task| task.is_trashed()) {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("Parent task {} not found", parent),
+        ));
+    }
+    let mut ancestor = Some(parent);
+    let mut seen = HashSet::new();
+    while let Some(current) = ancestor.filter(|&current| seen.insert(current)) {
+        if current == id {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Task {} cannot be a subtask of itself or of its own subtasks", id),
+            ));
+        }
+        ancestor = tasks.get(&current).and_then(|task| task.parent);
+    }
+    Ok(())
+}
+
+// How many of a task's subtasks are done, out of those not cancelled, or
+// `None` if it has no subtasks.
+pub fn progress<'a>(tasks: impl IntoIterator<Item = &'a Task>, id: usize) -> Option<(usize, usize)> {
+    let children = children(tasks, id);
+    if children.is_empty() {
+        return None;
+    }
+    let counted = children.iter().filter(|task| task.status != Status::Cancelled);
+    let done = counted.clone().filter(|task| task.status == Status::Done).count();
+    Some((done, counted.count()))
+}
+
+// Orders tasks depth-first, each listed task under its parent when the parent
+// is listed too, and pairs them with their depth. Siblings keep their order.
+pub fn arrange<'a>(tasks: &[&'a Task]) -> Vec<(usize, &'a Task)> {
+    let listed: HashSet<usize> = tasks.iter().map(|task| task.id.0).collect();
+    let mut arranged = Vec::with_capacity(tasks.len());
+    let mut visited = HashSet::new();
+    let roots = tasks
+        .iter()
+        .filter(|task| !task.parent.is_some_and(|parent| listed.contains(&parent)));
+    for root in roots {
+        visit(root, 0, tasks, &mut visited, &mut arranged);
+    }
+    // Only a damaged file can contain a cycle; show its tasks rather than drop them.
+    for task in tasks {
+        visit(task, 0, tasks, &mut visited, &mut arranged);
+    }
+    arranged
+}
+
+fn visit<'a>(
+    task: &'a Task,
+    depth: usize,
+    tasks: &[&'a Task],
+    visited: &mut HashSet<usize>,
+    arranged: &mut Vec<(usize, &'a Task)>,
+) {
+    if !visited.insert(task.id.0) {
+        return;
+    }
+    arranged.push((depth, task));
+    for child in tasks.iter().filter(|child| child.parent == Some(task.id.0)) {
+        visit(child, depth + 1, tasks, visited, arranged);
+    }
+}